@@ -0,0 +1,109 @@
+//! fetched files kept in memory and in Cache Storage
+//! A post is stored with the `hash` index_gen gives it in `posts.yml`, an entry with another
+//! hash is stale and fetched again. Files without a known hash live in memory only, for the
+//! rest of the visit.
+use crate::{
+    utils::{fetch, FetchError},
+    Route,
+};
+use fubuki_types::Post;
+use std::{cell::RefCell, collections::HashMap};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Cache, Headers, Response, ResponseInit};
+
+const STORAGE: &str = "fubuki-posts";
+/// the post hash of a stored response
+const HASH_HEADER: &str = "x-fubuki-hash";
+
+thread_local! {
+    /// url to text, and the hash it was stored with
+    static MEMORY: RefCell<HashMap<String, (Option<String>, String)>> = RefCell::default();
+    /// url to the current hash of the post
+    static HASHES: RefCell<HashMap<String, String>> = RefCell::default();
+}
+
+/// hashes of the posts in `posts.yml`
+pub(crate) fn track(posts: &[Post]) {
+    HASHES.with(|hashes| {
+        let mut hashes = hashes.borrow_mut();
+        for post in posts {
+            let route = Route::Post { id: post.url.clone() };
+            if let Some(url) = route.to_url() {
+                hashes.insert(url, post.hash.clone());
+            }
+        }
+    });
+}
+
+fn hash_of(url: &str) -> Option<String> {
+    HASHES.with(|hashes| hashes.borrow().get(url).cloned())
+}
+
+fn from_memory(url: &str, hash: &Option<String>) -> Option<String> {
+    MEMORY.with(|memory| match memory.borrow().get(url) {
+        Some((stored, text)) if stored == hash || hash.is_none() => Some(text.clone()),
+        _ => None,
+    })
+}
+
+async fn open() -> Option<Cache> {
+    let caches = gloo_utils::window().caches().ok()?;
+    let cache = JsFuture::from(caches.open(STORAGE)).await.ok()?;
+    cache.dyn_into().ok()
+}
+
+async fn from_storage(url: &str, hash: &str) -> Option<String> {
+    let cache = open().await?;
+    let response: Response = JsFuture::from(cache.match_with_str(url)).await.ok()?.dyn_into().ok()?;
+    if response.headers().get(HASH_HEADER).ok()?.as_deref() != Some(hash) {
+        return None;
+    }
+    JsFuture::from(response.text().ok()?).await.ok()?.as_string()
+}
+
+/// replaces the entry of an older hash
+async fn store(url: &str, hash: &str, text: &str) -> Option<()> {
+    let cache = open().await?;
+    let headers = Headers::new().ok()?;
+    headers.set(HASH_HEADER, hash).ok()?;
+    headers.set("content-type", "text/markdown; charset=utf-8").ok()?;
+    let response = Response::new_with_opt_str_and_init(Some(text), ResponseInit::new().headers(&headers)).ok()?;
+    JsFuture::from(cache.put_with_str(url, &response)).await.ok()?;
+    Some(())
+}
+
+/// text of `url`, from the network only when no fresh copy is kept
+pub(crate) async fn get(url: &str) -> Result<String, FetchError> {
+    let hash = hash_of(url);
+    if let Some(text) = from_memory(url, &hash) {
+        return Ok(text);
+    }
+    let stored = match &hash {
+        Some(hash) => from_storage(url, hash).await,
+        None => None,
+    };
+    let text = match stored {
+        Some(text) => text,
+        None => {
+            let text = fetch(url).await?;
+            if let Some(hash) = &hash {
+                if store(url, hash, &text).await.is_none() {
+                    log::warn!("can not keep {} in cache storage", url);
+                }
+            }
+            text
+        }
+    };
+    MEMORY.with(|memory| memory.borrow_mut().insert(url.to_owned(), (hash, text.clone())));
+    Ok(text)
+}
+
+/// fetch ahead, when the reader is about to open it
+pub(crate) fn prefetch(url: String) {
+    yew::platform::spawn_local(async move {
+        if let Err(e) = get(&url).await {
+            log::debug!("prefetch {} failed: {}", url, e);
+        }
+    });
+}
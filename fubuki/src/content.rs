@@ -1,18 +1,30 @@
 use crate::{
+    error::ErrorView,
+    scroll::use_scroll_ready,
     style::Colors,
-    utils::{use_remote, use_title},
+    utils::{site_author, use_head, use_remote, Fetched},
     Route,
 };
-use fubuki_types::{FrontMatter, Spoiler};
+use fubuki_types::{
+    date,
+    head::{self, Head},
+    markdown::{self, Heading},
+    FrontMatter, Spoiler,
+};
 use stylist::yew::{styled_component, use_style};
-use yew::{classes, html, use_context, virtual_dom::VNode, Html, HtmlResult, Properties};
+use yew::{classes, function_component, html, use_context, use_memo, Html, HtmlResult, Properties};
+use yew_router::Routable;
 
+mod nodes;
 mod style;
+mod toc;
 mod webmention;
 
 #[derive(PartialEq, Clone, Properties)]
 pub struct ArticleProps {
     pub route: Route,
+    /// markdown of the route
+    pub url: String,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -20,14 +32,56 @@ struct SpoilerProps {
     spoiler: Spoiler,
 }
 
-fn render_markdown(md: &str) -> Html {
-    let options = pulldown_cmark::Options::all();
-    let parser = pulldown_cmark::Parser::new_ext(md, options);
-    let mut html = String::new();
-    pulldown_cmark::html::push_html(&mut html, parser);
-    let node = gloo_utils::document().create_element("section").unwrap();
-    node.set_inner_html(&html);
-    VNode::VRef(node.into())
+fn render_markdown(md: &str) -> (Html, Vec<Heading>) {
+    let (nodes, toc) = markdown::render_tree(md);
+    (
+        html! { <section class="e-content">{ nodes::render(&nodes) }</section> },
+        toc,
+    )
+}
+
+#[derive(PartialEq, Properties)]
+struct EntryMetaProps {
+    id: String,
+    fm: FrontMatter,
+}
+
+/// the rest of the `h-entry`, date, author, categories and summary
+#[styled_component(EntryMeta)]
+fn entry_meta(props: &EntryMetaProps) -> Html {
+    let EntryMetaProps { id, fm } = props;
+    let colors: Colors = use_context().unwrap();
+    let style = use_style!(
+        "
+        margin-top: -1.5rem;
+        font-size: 0.8em;
+        color: ${shadow};
+        a, a:visited {
+            color: ${shadow};
+        }
+        .p-category {
+            margin-left: 0.5em;
+        }",
+        shadow = colors.shadow,
+    );
+    let published = date::from_jp_to_iso(fm.date.clone()).unwrap_or_default();
+    let (name, url) = site_author().unwrap_or_default();
+    let categories = std::iter::once(&fm.category)
+        .chain(fm.tags.iter())
+        .map(|c| html! { <span class="p-category">{ c }</span> })
+        .collect::<Html>();
+    html! {
+        <p class={style}>
+            <a class="u-url" href={format!("/post/{}", id)}>
+                <time class="dt-published" datetime={published}>{ &fm.date }</time>
+            </a>
+            if !name.is_empty() {
+                { " " }<a class="p-author h-card" href={url}>{ name }</a>
+            }
+            { categories }
+            <data class="p-summary" value={fm.summary.clone()}></data>
+        </p>
+    }
 }
 
 #[styled_component(SpoilerAlert)]
@@ -50,16 +104,51 @@ fn spoiler_alert(props: &SpoilerProps) -> Html {
     }
 }
 
-#[styled_component(Article)]
+fn page_head(route: &Route, title: &str, fm: Option<&FrontMatter>) -> Head {
+    let mut head = Head::page(title, route.to_path());
+    if let (Route::Post { .. }, Some(fm)) = (route, fm) {
+        if !fm.summary.is_empty() {
+            head.description = fm.summary.clone();
+        }
+        head.image = fm.image.clone();
+        let (author, author_url) = site_author().unwrap_or_default();
+        head.article = Some(head::Article {
+            published: date::from_jp_to_iso(fm.date.clone()).unwrap_or_default(),
+            section: fm.category.clone(),
+            tags: fm.tags.clone(),
+            author,
+            author_url,
+        });
+    }
+    head
+}
+
+#[derive(PartialEq, Properties)]
+struct PageProps {
+    route: Route,
+    page: String,
+}
+
+/// the fetched markdown, an error view has hooks of its own
+#[function_component(Article)]
 pub(crate) fn article(props: &ArticleProps) -> HtmlResult {
-    let ArticleProps { route } = props;
+    let route = props.route.clone();
+    let Fetched { result, retry } = use_remote(props.url.clone())?;
+    Ok(match result {
+        Ok(page) => html! { <Page {route} {page} /> },
+        Err(error) => html! { <ErrorView {error} {retry} /> },
+    })
+}
 
-    let page = use_remote(route.into_url())?;
+#[styled_component(Page)]
+fn page(props: &PageProps) -> Html {
+    let PageProps { route, page } = props;
     let is_post = route.is_post();
     let render_title = matches!(route, Route::Post { .. });
 
     let mut title = String::new();
     let mut spoiler = Spoiler::None;
+    let mut front_matter = None;
     // remove front matter
     let mut main = if page.starts_with("---\n") {
         if let Some(fm) = page.split("---\n").nth(1) {
@@ -67,8 +156,9 @@ pub(crate) fn article(props: &ArticleProps) -> HtmlResult {
             match serde_yaml::from_str::<FrontMatter>(fm) {
                 Ok(fm) => {
                     log::info!("get front_matter\n{:?}", &fm);
-                    title = fm.title;
-                    spoiler = fm.spoiler;
+                    title = fm.title.clone();
+                    spoiler = fm.spoiler.clone();
+                    front_matter = Some(fm);
                 }
                 Err(e) => {
                     log::error!("fm parser failed: {}", e);
@@ -90,23 +180,36 @@ pub(crate) fn article(props: &ArticleProps) -> HtmlResult {
     }
 
     let title_style = use_style!("margin-bottom: 2rem; margin-top: 0;");
-    use_title(title.clone());
+    use_head(page_head(route, &title, front_matter.as_ref()));
+    use_scroll_ready(true, route.clone());
     let h1 = if title.is_empty() || !render_title {
         html! { <></> }
     } else {
-        html! { <h1 class={title_style}>{ title }</h1> }
+        html! { <h1 class={classes![title_style, "p-name"]}>{ title }</h1> }
     };
 
     let colors: Colors = use_context().unwrap();
-    let class = use_style(style::article(&colors));
-    Ok(html! {
+    let article = use_style(style::article(&colors));
+    let highlight = use_style(style::highlight(&colors));
+    let class = classes![article, highlight];
+    let (main, headings) = (*use_memo(|main| render_markdown(main), main)).clone();
+    let entry = match (route, front_matter) {
+        (Route::Post { id }, Some(fm)) => html! { <EntryMeta id={id.clone()} {fm} /> },
+        _ => html! {},
+    };
+    let class = if is_post { classes![class, "h-entry"] } else { class };
+    html! {
         <article {class}>
             { h1 }
+            { entry }
             <SpoilerAlert {spoiler} />
-            { render_markdown(&main) }
             if is_post {
-                <webmention::Echo />
+                <toc::Toc {headings} />
+            }
+            { main }
+            if let Route::Post { id } = route {
+                <webmention::Echo id={id.clone()} />
             }
         </article>
-    })
+    }
 }
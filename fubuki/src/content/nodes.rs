@@ -0,0 +1,193 @@
+//! the markdown tree as yew nodes
+//! Links, images, headings and code blocks are components, the rest maps one to one.
+//! MathML has no place in the html namespace yew creates elements in, so a `<math>` is
+//! created in its own namespace and the browser parses the rest under it.
+use crate::{style::Colors, Route};
+use fubuki_types::markdown::tree::{Element, Node};
+use stylist::yew::{styled_component, use_style};
+use yew::{
+    classes, function_component, html, use_context, use_state,
+    virtual_dom::{VNode, VTag},
+    AttrValue, Callback, Html, MouseEvent, Properties,
+};
+use yew_router::{hooks::use_navigator, Routable};
+
+const MATHML: &str = "http://www.w3.org/1998/Math/MathML";
+
+pub(super) fn render(nodes: &[Node]) -> Html {
+    nodes.iter().map(node).collect()
+}
+
+fn node(node: &Node) -> Html {
+    let element = match node {
+        Node::Text(text) => return html! { text.clone() },
+        Node::Element(element) => element.clone(),
+    };
+    match element.name {
+        "a" => html! { <Anchor {element} /> },
+        "img" => html! { <Image {element} /> },
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => html! { <Heading {element} /> },
+        "pre" => html! { <CodeBlock {element} /> },
+        "math" => math(&element),
+        _ => tag(&element, render(&element.children)),
+    }
+}
+
+fn tag(element: &Element, children: Html) -> Html {
+    let mut tag = VTag::new(element.name);
+    for (key, value) in &element.attributes {
+        tag.add_attribute(key, value.clone());
+    }
+    tag.add_child(children);
+    tag.into()
+}
+
+fn attribute(element: &Element, name: &str) -> Option<AttrValue> {
+    element.attribute(name).map(|value| AttrValue::from(value.to_owned()))
+}
+
+fn math(element: &Element) -> Html {
+    let Ok(math) = gloo_utils::document().create_element_ns(Some(MATHML), "math") else {
+        return html! {};
+    };
+    for (key, value) in &element.attributes {
+        math.set_attribute(key, value).ok();
+    }
+    math.set_inner_html(&element.children.iter().map(Node::to_html).collect::<String>());
+    VNode::VRef(math.into())
+}
+
+#[derive(PartialEq, Properties)]
+struct ElementProps {
+    element: Element,
+}
+
+/// pages of the blog open in place, the rest as plain links
+#[function_component(Anchor)]
+fn anchor(props: &ElementProps) -> Html {
+    let element = &props.element;
+    let navigator = use_navigator();
+    let href = element.attribute("href").unwrap_or_default().to_owned();
+    let route = (href.starts_with('/') && !href.starts_with("//") && !href.contains(['?', '#']))
+        .then(|| Route::recognize(&href))
+        .flatten()
+        .filter(|route| *route != Route::NotFound);
+    let onclick = Callback::from(move |e: MouseEvent| {
+        if e.button() != 0 || e.ctrl_key() || e.meta_key() || e.shift_key() || e.alt_key() {
+            return;
+        }
+        if let (Some(route), Some(navigator)) = (&route, &navigator) {
+            e.prevent_default();
+            navigator.push(route);
+        }
+    });
+    let rel = attribute(element, "rel").or_else(|| href.starts_with("http").then(|| AttrValue::from("noopener")));
+    html! {
+        <a href={href.clone()} {rel} {onclick}
+            id={attribute(element, "id")}
+            class={element.attribute("class").map(str::to_owned)}
+            title={attribute(element, "title")}
+            lang={attribute(element, "lang")}>
+            { render(&element.children) }
+        </a>
+    }
+}
+
+/// loaded lazily, a broken one leaves its alt
+#[styled_component(Image)]
+fn image(props: &ElementProps) -> Html {
+    let element = &props.element;
+    let colors: Colors = use_context().unwrap();
+    let broken = use_state(|| false);
+    let style = use_style!("color: ${shadow}; font-style: italic;", shadow = colors.shadow);
+    let alt = attribute(element, "alt").unwrap_or_default();
+    if *broken {
+        return html! { <span class={style} role="img" aria-label={alt.clone()}>{ alt }</span> };
+    }
+    let onerror = Callback::from(move |_| broken.set(true));
+    html! {
+        <img src={attribute(element, "src")} {alt} {onerror}
+            title={attribute(element, "title")}
+            id={attribute(element, "id")}
+            class={element.attribute("class").map(str::to_owned)}
+            width={attribute(element, "width")}
+            height={attribute(element, "height")}
+            loading={attribute(element, "loading").unwrap_or_else(|| AttrValue::from("lazy"))}
+            decoding="async" />
+    }
+}
+
+/// headings with an id link to themselves
+#[styled_component(Heading)]
+fn heading(props: &ElementProps) -> Html {
+    let element = &props.element;
+    let style = use_style!(
+        "
+        a.anchor {
+            margin-left: 0.3em;
+            opacity: 0;
+            text-decoration: none;
+        }
+        &:hover a.anchor, a.anchor:focus {
+            opacity: 1;
+        }
+        a.anchor::after {
+            content: none;
+        }"
+    );
+    let Some(id) = element.attribute("id") else {
+        return tag(element, render(&element.children));
+    };
+    let children = html! {
+        <>
+            { render(&element.children) }
+            <a class="anchor" href={format!("#{}", id)} aria-label="この見出しへのリンク">{ "#" }</a>
+        </>
+    };
+    let mut heading = VTag::new(element.name);
+    for (key, value) in &element.attributes {
+        heading.add_attribute(key, value.clone());
+    }
+    heading.add_attribute(
+        "class",
+        classes![element.attribute("class").map(str::to_owned), style].to_string(),
+    );
+    heading.add_child(children);
+    heading.into()
+}
+
+/// code blocks name their language
+#[styled_component(CodeBlock)]
+fn code_block(props: &ElementProps) -> Html {
+    let element = &props.element;
+    let colors: Colors = use_context().unwrap();
+    let style = use_style!(
+        "
+        position: relative;
+        .language {
+            position: absolute;
+            top: 0.2em;
+            right: 0.5em;
+            font-size: 0.8em;
+            color: ${shadow};
+            user-select: none;
+        }",
+        shadow = colors.shadow,
+    );
+    let language = element.children.iter().find_map(|child| match child {
+        Node::Element(code) if code.name == "code" => code
+            .attribute("class")?
+            .split_whitespace()
+            .find_map(|class| class.strip_prefix("language-"))
+            .map(str::to_owned),
+        _ => None,
+    });
+    html! {
+        <div class={style}>
+            if let Some(language) = language {
+                <span class="language">{ language }</span>
+            }
+            { tag(element, render(&element.children)) }
+        </div>
+    }
+}
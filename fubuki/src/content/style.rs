@@ -2,9 +2,10 @@ use crate::style::Colors;
 use stylist::{css, StyleSource};
 
 #[inline]
-pub(super) fn article<'a>(colors: &Colors) -> StyleSource {
+pub(super) fn article(colors: &Colors) -> StyleSource {
     css!(
         r#"
+position: relative;
 h1,h2,h3,h4,h5 {
     color: ${bold};
 }
@@ -111,6 +112,7 @@ hr {
 }
 img {
     max-width: 100%;
+    height: auto;
 }
 img.task-marker {
     display: inline;
@@ -152,6 +154,35 @@ sup a:visited::after {
 div.footnote-definition p {
     display: inline;
 }
+iframe.embed {
+    display: block;
+    width: 100%;
+    border: none;
+}
+iframe.youtube {
+    height: auto;
+    aspect-ratio: 16 / 9;
+}
+figure {
+    margin: 1em 0;
+}
+figcaption {
+    color: ${shadow};
+    font-size: 0.9em;
+    text-align: center;
+}
+details summary {
+    cursor: pointer;
+}
+code.math-error,
+code.shortcode-error {
+    color: ${red};
+    border-color: ${red};
+}
+math[display="block"] {
+    margin: 1em 0;
+    overflow-x: auto;
+}
 "#,
         normal = colors.normal,
         bold = colors.bold,
@@ -164,3 +195,49 @@ div.footnote-definition p {
         colors_fg = colors.colors_fg,
     )
 }
+
+/// colors for the `hl-` classes index_gen puts on code blocks
+#[inline]
+pub(super) fn highlight(colors: &Colors) -> StyleSource {
+    css!(
+        r#"
+pre.hl .hl-comment {
+    color: ${comment};
+    font-style: italic;
+}
+pre.hl .hl-keyword,
+pre.hl .hl-storage {
+    color: ${keyword};
+}
+pre.hl .hl-string {
+    color: ${string};
+}
+pre.hl .hl-constant,
+pre.hl .hl-support.hl-constant {
+    color: ${constant};
+}
+pre.hl .hl-entity.hl-name.hl-function,
+pre.hl .hl-support.hl-function {
+    color: ${function};
+}
+pre.hl .hl-entity.hl-name.hl-type,
+pre.hl .hl-entity.hl-name.hl-class,
+pre.hl .hl-support.hl-type,
+pre.hl .hl-support.hl-class {
+    color: ${type_};
+}
+pre.hl .hl-invalid {
+    color: ${colors_fg};
+    background-color: ${red_bg};
+}
+"#,
+        comment = colors.code_comment,
+        keyword = colors.code_keyword,
+        string = colors.code_string,
+        constant = colors.code_constant,
+        function = colors.code_function,
+        type_ = colors.code_type,
+        colors_fg = colors.colors_fg,
+        red_bg = colors.red_bg,
+    )
+}
@@ -0,0 +1,100 @@
+use fubuki_types::markdown::Heading;
+use stylist::yew::{styled_component, use_media_query, use_style};
+use yew::{html, use_context, Html, Properties};
+
+use crate::style::Colors;
+
+#[derive(Clone, PartialEq, Properties)]
+pub(super) struct TocProps {
+    pub headings: Vec<Heading>,
+}
+
+/// table of contents
+/// sticky sidebar on wide screens, collapsible block on small ones.
+#[styled_component(Toc)]
+pub(super) fn toc(props: &TocProps) -> Html {
+    let TocProps { headings } = props;
+    let colors: Colors = use_context().unwrap();
+    let is_on_small_device = use_media_query("max-width: 1036px");
+
+    let list = use_style!(
+        r#"
+        margin: 0;
+        padding: 0;
+        list-style-type: none;
+        font-size: 0.9em;
+        li {
+            line-height: 1.6em;
+        }
+        a, a:visited {
+            color: ${normal};
+            text-decoration: none;
+        }
+        a::after {
+            content: none;
+        }
+        a:hover {
+            color: ${bold};
+        }"#,
+        normal = colors.normal,
+        bold = colors.bold,
+    );
+    let sidebar = use_style!(
+        "
+        position: absolute;
+        top: 0;
+        bottom: 0;
+        left: 100%;
+        width: 200px;
+        margin-left: 2em;
+        nav {
+            position: sticky;
+            top: 2em;
+            padding-left: 1em;
+            border-left: 0.1em solid ${shadow};
+        }",
+        shadow = colors.shadow,
+    );
+    let block = use_style!(
+        "
+        margin-bottom: 1em;
+        padding: 0.5em;
+        background-color: ${underground};
+        summary {
+            cursor: pointer;
+            color: ${bold};
+        }",
+        underground = colors.underground,
+        bold = colors.bold,
+    );
+
+    if headings.len() < 2 {
+        return html! { <></> };
+    }
+    let top = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let items = headings
+        .iter()
+        .map(|h| {
+            let indent = format!("padding-left: {}em;", h.level - top);
+            html! {
+                <li style={indent}><a href={format!("#{}", h.id)}>{ &h.title }</a></li>
+            }
+        })
+        .collect::<Html>();
+    let list = html! { <ol class={list}>{ items }</ol> };
+
+    if is_on_small_device {
+        html! {
+            <details class={block}>
+                <summary>{ "目次" }</summary>
+                { list }
+            </details>
+        }
+    } else {
+        html! {
+            <aside class={sidebar}>
+                <nav>{ list }</nav>
+            </aside>
+        }
+    }
+}
@@ -1,50 +1,192 @@
-use serde::{Deserialize, Serialize};
-use stylist::yew::styled_component;
-use yew::{html, Html};
+use fubuki_types::webmention::{Author, Feed, Mention, Thread};
+use stylist::yew::{styled_component, use_style};
+use yew::{html, use_context, use_state_eq, Callback, Html, Properties};
 
-use crate::utils::use_json;
+use crate::{style::Colors, utils::use_json, Route};
 
-#[derive(Serialize, Deserialize, PartialEq, Clone)]
-struct Webmention {
-    url: String,
+/// top level replies per page
+const REPLIES_PER_PAGE: usize = 10;
+
+#[derive(PartialEq, Properties)]
+pub(super) struct EchoProps {
+    /// post id
+    pub id: String,
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
-struct Webmentions {
-    children: Vec<Webmention>,
+#[derive(PartialEq, Properties)]
+struct FacepileProps {
+    title: &'static str,
+    mentions: Vec<Mention>,
 }
 
-fn get_current() -> Option<String> {
-    let current = gloo_utils::document().location()?;
-    let url = format!("{}{}", current.origin().ok()?, current.pathname().ok()?);
-    log::info!("current at: {url}");
+fn name(author: &Author) -> &str {
+    if author.name.is_empty() {
+        &author.url
+    } else {
+        &author.name
+    }
+}
 
-    Some(url)
+fn avatar(author: &Author) -> Html {
+    if author.photo.is_empty() {
+        html! { <span class="avatar">{ name(author).chars().next().unwrap_or('?') }</span> }
+    } else {
+        html! { <img class="avatar" src={author.photo.clone()} alt={name(author).to_owned()} loading="lazy" /> }
+    }
 }
 
-const TOKEN: &str = "iLobGtxghdo0MnNFqW7bbA";
+/// likes or reposts as a row of faces
+#[styled_component(Facepile)]
+fn facepile(props: &FacepileProps) -> Html {
+    let FacepileProps { title, mentions } = props;
+    if mentions.is_empty() {
+        return html! { <></> };
+    }
+    let faces = mentions
+        .iter()
+        .map(|m| {
+            let href = if m.author.url.is_empty() {
+                m.url.clone()
+            } else {
+                m.author.url.clone()
+            };
+            html! {
+                <li><a {href} title={name(&m.author).to_owned()}>{ avatar(&m.author) }</a></li>
+            }
+        })
+        .collect::<Html>();
+    html! {
+        <div class="facepile">
+            <h4>{ format!("{} {}", title, mentions.len()) }</h4>
+            <ul>{ faces }</ul>
+        </div>
+    }
+}
 
+fn reply(thread: &Thread) -> Html {
+    let Thread { mention, replies } = thread;
+    let text = mention.content.as_ref().map(|c| c.text.clone()).unwrap_or_default();
+    let date = mention.date().chars().take(10).collect::<String>();
+    let replies = if replies.is_empty() {
+        html! {}
+    } else {
+        html! { <ol>{ replies.iter().map(reply).collect::<Html>() }</ol> }
+    };
+    html! {
+        <li key={mention.url.clone()}>
+            <div class="author">
+                { avatar(&mention.author) }
+                <a href={mention.author.url.clone()}>{ name(&mention.author) }</a>
+                <a class="date" href={mention.url.clone()}><time datetime={mention.date().to_owned()}>{ date }</time></a>
+            </div>
+            <p>{ text }</p>
+            { replies }
+        </li>
+    }
+}
+
+/// mentions are baked by index_gen into a static file
 #[styled_component(Echo)]
-pub(super) fn echo() -> Html {
-    let url = get_current().unwrap();
-    let mentions: Vec<Webmention> = use_json(format!(
-        "https://webmention.io/api/mentions.jf2?target={url}&token={TOKEN}"
-    ))
-    .unwrap_or_default();
+pub(super) fn echo(props: &EchoProps) -> Html {
+    // a post without mentions has no file, nothing to show either way
+    let feed = use_json::<_, Feed>(Route::mentions_url(&props.id))
+        .and_then(|fetched| fetched.result.ok())
+        .unwrap_or_default();
+    let page = use_state_eq(|| 0);
+    let colors: Colors = use_context().unwrap();
+    let style = use_style!(
+        r#"
+        ul, ol {
+            list-style-type: none;
+            padding-left: 0;
+        }
+        ol ol {
+            padding-left: 1.5em;
+            border-left: 0.1em solid ${underground};
+        }
+        .facepile ul {
+            display: flex;
+            flex-wrap: wrap;
+            gap: 0.3em;
+        }
+        .facepile a::after {
+            content: none;
+        }
+        .avatar {
+            display: inline-block;
+            width: 2em;
+            height: 2em;
+            line-height: 2em;
+            text-align: center;
+            border-radius: 50%;
+            object-fit: cover;
+            vertical-align: middle;
+            background-color: ${underground};
+        }
+        .author {
+            display: flex;
+            align-items: center;
+            gap: 0.5em;
+        }
+        .date {
+            margin-left: auto;
+            font-size: 0.8em;
+            color: ${shadow};
+        }
+        nav button {
+            margin-right: 1em;
+        }"#,
+        underground = colors.underground,
+        shadow = colors.shadow,
+    );
 
-    if mentions.is_empty() {
+    let grouped = feed.group();
+    if feed.children.is_empty() {
         return html! { <></> };
     }
-    let mentions_list = mentions
-        .into_iter()
-        .map(|m| html! {<li><a href={ m.url.clone() }> { m.url } </a></li>})
+    let pages = grouped.replies.len().div_ceil(REPLIES_PER_PAGE);
+    let current = (*page).min(pages.saturating_sub(1));
+    let replies = grouped
+        .replies
+        .iter()
+        .skip(current * REPLIES_PER_PAGE)
+        .take(REPLIES_PER_PAGE)
+        .map(reply)
+        .collect::<Html>();
+    let flip = |to: usize, label: &'static str, disabled: bool| {
+        let page = page.clone();
+        let onclick = Callback::from(move |_| page.set(to));
+        html! { <button {onclick} {disabled}>{ label }</button> }
+    };
+    let nav = if pages > 1 {
+        html! {
+            <nav>
+                { flip(current.saturating_sub(1), "← Prev", current == 0) }
+                { flip(current + 1, "Next →", current + 1 >= pages) }
+            </nav>
+        }
+    } else {
+        html! {}
+    };
+    let mentions = grouped
+        .mentions
+        .iter()
+        .map(|m| html! { <li><a href={m.url.clone()}>{ name(&m.author) }</a>{ " " }{ &m.url }</li> })
         .collect::<Html>();
     html! {
-        <div>
+        <section class={style}>
             <h3>{ "Echo" }</h3>
-            <ul>
-            { mentions_list }
-            </ul>
-        </div>
+            <Facepile title="いいね" mentions={grouped.likes.clone()} />
+            <Facepile title="リポスト" mentions={grouped.reposts.clone()} />
+            if !grouped.replies.is_empty() {
+                <h4>{ format!("返信 {}", grouped.replies.len()) }</h4>
+                <ol>{ replies }</ol>
+                { nav }
+            }
+            if !grouped.mentions.is_empty() {
+                <h4>{ "言及" }</h4>
+                <ul>{ mentions }</ul>
+            }
+        </section>
     }
 }
@@ -0,0 +1,85 @@
+//! pages for what could not be shown
+use crate::{
+    scroll::use_scroll_ready,
+    style::Colors,
+    utils::{use_head, FetchError},
+    Route,
+};
+use fubuki_types::head::Head;
+use stylist::{
+    css,
+    yew::{styled_component, use_style},
+    StyleSource,
+};
+use yew::{html, use_context, Callback, Html, Properties};
+use yew_router::{components::Link, hooks::use_location};
+
+fn message(colors: &Colors) -> StyleSource {
+    css!(
+        "
+        padding-top: 2rem;
+        h1 {
+            margin-top: 0;
+            color: ${bold};
+        }
+        button {
+            cursor: pointer;
+            padding: 0.3em 0.8em;
+            color: ${bold};
+            background-color: ${bg2};
+            border: solid ${underground} 0.1em;
+            transition: border-color 0.3s;
+        }
+        button:hover {
+            border-color: ${normal};
+        }",
+        bold = colors.bold,
+        normal = colors.normal,
+        bg2 = colors.bg2,
+        underground = colors.underground,
+    )
+}
+
+#[styled_component(NotFound)]
+pub(crate) fn not_found() -> Html {
+    let colors: Colors = use_context().unwrap();
+    let path = use_location()
+        .map(|location| location.path().to_owned())
+        .unwrap_or_default();
+    use_head(Head::page("404", path));
+    use_scroll_ready(true, ());
+    let style = use_style(message(&colors));
+    html! {
+        <section class={style}>
+            <h1>{ "404" }</h1>
+            <p>{ "お探しのページは見つかりませんでした。" }</p>
+            <Link<Route> to={Route::Main}>{ "トップへ戻る" }</Link<Route>>
+        </section>
+    }
+}
+
+#[derive(PartialEq, Properties)]
+pub(crate) struct ErrorViewProps {
+    pub error: FetchError,
+    pub retry: Callback<()>,
+}
+
+/// a failed fetch, with a button to try again
+#[styled_component(ErrorView)]
+pub(crate) fn error_view(props: &ErrorViewProps) -> Html {
+    let ErrorViewProps { error, retry } = props;
+    let colors: Colors = use_context().unwrap();
+    use_scroll_ready(true, error.clone());
+    let style = use_style(message(&colors));
+    if *error == FetchError::NotFound {
+        return html! { <NotFound /> };
+    }
+    let onclick = retry.reform(|_| ());
+    html! {
+        <section class={style} role="alert">
+            <h1>{ "読み込めませんでした" }</h1>
+            <p>{ error.to_string() }</p>
+            <button {onclick}>{ "もう一度" }</button>
+        </section>
+    }
+}
@@ -1,19 +1,27 @@
+mod cache;
 mod content;
+mod error;
 mod loading;
 pub mod logger;
 mod navbar;
+pub mod offline;
 mod posts;
+mod scroll;
 mod style;
 pub(crate) mod utils;
 
 use stylist::yew::{styled_component, use_media_query, use_style, Global};
-use yew::{classes, html, use_context, ContextProvider, Html, Suspense};
+use yew::{
+    classes, html, platform::spawn_local, use_context, use_effect_with_deps, use_state, Callback, ContextProvider,
+    Html, Suspense,
+};
 use yew_router::{BrowserRouter, Routable, Switch};
 
 use content::Article;
+use error::NotFound;
 use navbar::Navbar;
 use posts::Posts;
-use style::{Colors, Layout};
+use style::{Colors, Layout, Palette, Scheme, ThemeSwitch, Themes};
 
 use crate::loading::Loading;
 
@@ -29,6 +37,9 @@ pub enum Route {
     About,
     #[at("/links")]
     Links,
+    #[not_found]
+    #[at("/404")]
+    NotFound,
 }
 
 impl Route {
@@ -36,19 +47,26 @@ impl Route {
         matches!(self, Self::Post { .. })
     }
 
-    fn into_url(&self) -> String {
-        match self {
+    /// what the route shows, `None` for a route without content
+    fn to_url(&self) -> Option<String> {
+        Some(match self {
             Route::Posts => "/posts.yml".to_owned(),
             Route::Post { id } => format!("/public/post/{}.md", id),
             Route::Main => "/public/index.md".to_owned(),
             Route::About => "/public/about.md".to_owned(),
             Route::Links => "/public/links.md".to_owned(),
-        }
+            Route::NotFound => return None,
+        })
     }
 
     fn posts_url() -> &'static str {
         "/posts.yml"
     }
+
+    /// webmentions of a post, baked by index_gen
+    fn mentions_url(id: &str) -> String {
+        format!("/mentions/{}.json", id)
+    }
 }
 
 const CC3: &str = "https://creativecommons.org/licenses/by-nc-sa/3.0/deed.ja";
@@ -94,13 +112,56 @@ fn footer() -> Html {
 fn switch(route: Route) -> Html {
     match route {
         Route::Posts => html! { <Posts /> },
-        route => html! { <Article {route} /> },
+        route => match route.to_url() {
+            Some(url) => html! { <Article {route} {url} /> },
+            None => html! { <NotFound /> },
+        },
     }
 }
 
 #[styled_component(Blog)]
 pub fn blog() -> Html {
-    let colors = style::colors(style::Theme::Light);
+    let prefers_dark = use_media_query("(prefers-color-scheme: dark)");
+    let themes = use_state(Themes::builtin);
+    let chosen = use_state(style::stored_theme);
+    // palettes edited in public/themes.yml apply without a rebuild
+    {
+        let themes = themes.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    if let Some(loaded) = style::load_themes().await {
+                        themes.set(loaded);
+                    }
+                });
+            },
+            (),
+        );
+    }
+    let scheme = if prefers_dark { Scheme::Dark } else { Scheme::Light };
+    let palette = chosen
+        .as_deref()
+        .and_then(|name| themes.get(name))
+        .unwrap_or_else(|| themes.preferred(scheme))
+        .clone();
+    let colors = palette.colors.clone();
+    let theme_switch = ThemeSwitch {
+        next: themes.next(&palette.name).clone(),
+        set: Callback::from(move |palette: Palette| {
+            style::store_theme(&palette);
+            chosen.set(Some(palette.name));
+        }),
+    };
+    // main.scss reads it for what is drawn before the app
+    use_effect_with_deps(
+        |(name, scheme)| {
+            if let Some(root) = gloo_utils::document().document_element() {
+                root.set_attribute("data-theme", name).ok();
+                root.set_attribute("data-scheme", scheme.name()).ok();
+            }
+        },
+        (palette.name.clone(), palette.scheme),
+    );
     let is_on_small_device = use_media_query("max-width: 1036px");
     // layout
     let top = if is_on_small_device { 2.0 } else { 3.0 };
@@ -118,8 +179,12 @@ pub fn blog() -> Html {
         "
         font-size: 12pt;
         color: ${fg};
+        background-color: ${bg};
+        color-scheme: ${scheme};
         overflow-y: scroll;",
-        fg = colors.normal
+        fg = colors.normal,
+        bg = colors.page_bg,
+        scheme = palette.scheme.name(),
     );
 
     let fallback = html! {
@@ -129,15 +194,19 @@ pub fn blog() -> Html {
         <>
         <Global css={global}/>
         <ContextProvider<Colors> context={colors}>
+        <ContextProvider<ThemeSwitch> context={theme_switch}>
         <BrowserRouter>
             <Navbar />
             <main {class}>
-                <Suspense {fallback}>
-                    <Switch<Route> render={switch} />
-                </Suspense>
+                <scroll::Scroll>
+                    <Suspense {fallback}>
+                        <Switch<Route> render={switch} />
+                    </Suspense>
+                </scroll::Scroll>
             </main>
         </BrowserRouter>
             <Footer />
+        </ContextProvider<ThemeSwitch>>
         </ ContextProvider<Colors>>
         </>
     }
@@ -1,7 +1,12 @@
-use fubuki::{logger, Blog};
+use fubuki::{logger, offline, Blog};
 
 fn main() {
     logger::init();
+    // static markup written by index_gen, the app takes over from here
+    if let Some(prerender) = gloo_utils::document().get_element_by_id("prerender") {
+        prerender.remove();
+    }
+    offline::register();
     // CSR
     yew::Renderer::<Blog>::new().render();
 }
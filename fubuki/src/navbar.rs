@@ -1,6 +1,9 @@
-use crate::{style::Colors, Route};
+use crate::{
+    style::{Colors, Scheme, ThemeSwitch},
+    Route,
+};
 use stylist::yew::{styled_component, use_style};
-use yew::{classes, html, use_context, Html, Properties};
+use yew::{classes, html, use_context, Callback, Html, Properties};
 use yew_router::{components::Link, hooks::use_route};
 
 #[derive(PartialEq, Properties)]
@@ -83,6 +86,35 @@ fn button(props: &ButtonProps) -> Html {
     }
 }
 
+/// goes through the palettes, remembered in `localStorage`
+#[styled_component(ThemeToggle)]
+fn theme_toggle() -> Html {
+    let ThemeSwitch { next, set } = use_context().unwrap();
+    let colors: Colors = use_context().unwrap();
+    let style = use_style!(
+        "
+        margin-left: 0.4rem;
+        padding: 0 0.3rem;
+        cursor: pointer;
+        color: ${fg};
+        transition: color 0.3s;
+        &:hover {
+            color: ${bold};
+        }",
+        fg = colors.shadow,
+        bold = colors.bold,
+    );
+    let mark = match next.scheme {
+        Scheme::Light => "☀",
+        Scheme::Dark => "☾",
+    };
+    let label = format!("テーマ: {}", next.name);
+    let onclick = Callback::from(move |_| set.emit(next.clone()));
+    html! {
+        <button class={style} {onclick} title={label.clone()} aria-label={label}>{ mark }</button>
+    }
+}
+
 #[styled_component(Navbar)]
 pub fn navbar() -> Html {
     let ima: Route = use_route().unwrap();
@@ -106,6 +138,7 @@ pub fn navbar() -> Html {
         <nav class={classes!(navbar, "navbar")}>
             <Button item={Route::Main} ima={ima.clone()} />
             <span class={site_title}>{ "島風造船所" }</span>
+            <ThemeToggle />
             <div class={nav_right}>
                 <Button item={Route::Posts} ima={ima.clone()} />
                 <Button item={Route::Links} ima={ima.clone()} />
@@ -0,0 +1,61 @@
+//! the service worker index_gen writes, and what it lets the reader open offline
+use crate::{style::Colors, Route};
+use stylist::yew::{styled_component, use_style};
+use wasm_bindgen_futures::JsFuture;
+use yew::{html, platform::spawn_local, use_context, use_effect_with_deps, use_state, Html, Properties};
+
+/// `sw.js` only exists in built sites, trunk serve has none
+pub fn register() {
+    let container = gloo_utils::window().navigator().service_worker();
+    spawn_local(async move {
+        if let Err(e) = JsFuture::from(container.register("/sw.js")).await {
+            log::info!("no service worker: {:?}", e);
+        }
+    });
+}
+
+/// kept by the worker or by the fetch cache
+async fn is_cached(url: &str) -> bool {
+    let Ok(caches) = gloo_utils::window().caches() else {
+        return false;
+    };
+    match JsFuture::from(caches.match_with_str(url)).await {
+        Ok(response) => !response.is_undefined(),
+        Err(_) => false,
+    }
+}
+
+#[derive(PartialEq, Properties)]
+pub(crate) struct OfflineMarkProps {
+    pub id: String,
+}
+
+/// shown on a post of the list that can be read offline
+#[styled_component(OfflineMark)]
+pub(crate) fn offline_mark(props: &OfflineMarkProps) -> Html {
+    let colors: Colors = use_context().unwrap();
+    let cached = use_state(|| false);
+    {
+        let cached = cached.clone();
+        use_effect_with_deps(
+            move |id| {
+                let route = Route::Post { id: id.clone() };
+                if let Some(url) = route.to_url() {
+                    spawn_local(async move { cached.set(is_cached(&url).await) });
+                }
+            },
+            props.id.clone(),
+        );
+    }
+    let style = use_style!(
+        "margin-left: 0.5em; padding: 0.3em 0.5em; color: ${fg}; background-color: ${bg};",
+        fg = colors.colors_fg,
+        bg = colors.green_bg
+    );
+    if !*cached {
+        return html! {};
+    }
+    html! {
+        <span class={style} title="オフラインでも読めます">{ "オフライン" }</span>
+    }
+}
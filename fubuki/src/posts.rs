@@ -1,13 +1,29 @@
 /// article list component
 use crate::{
+    cache,
+    error::ErrorView,
+    offline::OfflineMark,
+    scroll::use_scroll_ready,
     style::Colors,
-    utils::{use_json, use_title},
+    utils::{page_size, use_head, use_json, Fetched},
     Route,
 };
-pub use fubuki_types::{Post, PostList};
+pub use fubuki_types::Post;
+use fubuki_types::{date, head::Head};
+use serde::{Deserialize, Serialize};
 use stylist::yew::{styled_component, use_style};
-use yew::{classes, html, use_context, use_state_eq, Callback, Html, Properties};
-use yew_router::components::Link;
+use yew::{classes, html, use_context, use_effect_with_deps, use_memo, Callback, Children, Classes, Html, Properties};
+use yew_router::{
+    components::Link,
+    hooks::{use_location, use_navigator},
+    Routable,
+};
+
+/// `?page=N`, from 1
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct PageQuery {
+    pub page: usize,
+}
 
 #[derive(Clone, PartialEq)]
 pub enum PageNumMod {
@@ -24,20 +40,18 @@ impl PageNumMod {
     }
 }
 
-#[inline]
-fn page_count(postlist: &PostList) -> usize {
-    (postlist.len() + 4) / 5
-}
-
-fn page_flip(page_num: usize, msg: &PageNumMod, page_count: usize) -> usize {
-    let mut new_page_num = match msg {
-        PageNumMod::Prev => page_num.saturating_sub(1),
-        PageNumMod::Next => page_num.saturating_add(1),
-    };
-    if new_page_num > page_count {
-        new_page_num = page_count;
+/// pages to link, `None` is a gap
+/// the first, the last and two around the current one.
+fn pager(current: usize, count: usize) -> Vec<Option<usize>> {
+    let mut pages = Vec::new();
+    for page in 1..=count {
+        if page == 1 || page == count || page.abs_diff(current) <= 2 {
+            pages.push(Some(page));
+        } else if pages.last() != Some(&None) {
+            pages.push(None);
+        }
     }
-    new_page_num
+    pages
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -98,15 +112,26 @@ fn article_item(props: &ArticleItemProps) -> Html {
         bg = colors.red_bg
     );
 
+    let published = date::from_jp_to_iso(post.date.clone()).unwrap_or_default();
+    let prefetch = {
+        let url = route.to_url();
+        Callback::from(move |()| {
+            if let Some(url) = &url {
+                cache::prefetch(url.clone());
+            }
+        })
+    };
     html! {
-        <article class={split_line}>
-            <h2>
-                <Link<Route> to={route} classes={classes![post_title]}>{&post.title}</Link<Route>>
+        <article class={classes![split_line, "h-entry"]}>
+            <h2 class="p-name" onmouseover={prefetch.reform(|_| ())} onfocusin={prefetch.reform(|_| ())}>
+                <Link<Route> to={route} classes={classes![post_title, "u-url"]}>{&post.title}</Link<Route>>
             </h2>
-            <p>{ &post.summary }</p>
+            <p class="p-summary">{ &post.summary }</p>
             <small>
-                <time class={time}>{ &post.date }</time>
-                <span class={category}>{ &post.category }</span>
+                <time class={classes![time, "dt-published"]} datetime={published}>{ &post.date }</time>
+                <span class={classes![category, "p-category"]}>{ &post.category }</span>
+                <OfflineMark id={post.url.clone()} />
+                { for post.tags.iter().map(|tag| html! { <data class="p-category" value={tag.clone()}></data> }) }
                 {
                     if post.has_spoiler() {
                         html! {
@@ -121,20 +146,86 @@ fn article_item(props: &ArticleItemProps) -> Html {
     }
 }
 
+#[derive(PartialEq, Properties)]
+struct PageLinkProps {
+    page: usize,
+    #[prop_or_default]
+    classes: Classes,
+    #[prop_or_default]
+    current: bool,
+    children: Children,
+}
+
+/// page 1 is the plain `/posts`
+#[styled_component(PageLink)]
+fn page_link(props: &PageLinkProps) -> Html {
+    let PageLinkProps {
+        page,
+        classes,
+        current,
+        children,
+    } = props;
+    let query = (*page > 1).then_some(PageQuery { page: *page });
+    if *current {
+        return html! { <span class={classes.clone()} aria-current="page">{ children.clone() }</span> };
+    }
+    html! {
+        <Link<Route, PageQuery> to={Route::Posts} {query} classes={classes.clone()}>{ children.clone() }</Link<Route, PageQuery>>
+    }
+}
+
 #[styled_component(Posts)]
 pub fn posts() -> Html {
-    let postlist: Vec<Post> = use_json(Route::posts_url()).unwrap_or_default();
-    let page_num = use_state_eq(|| 0);
+    let (postlist, failed) = match use_json::<_, Vec<Post>>(Route::posts_url()) {
+        Some(Fetched { result: Ok(list), .. }) => (list, None),
+        Some(Fetched {
+            result: Err(error),
+            retry,
+        }) => (Vec::new(), Some((error, retry))),
+        None => (Vec::new(), None),
+    };
+    // hashes of the posts tell the cache which copies are stale
+    use_effect_with_deps(|list: &Vec<Post>| cache::track(list), postlist.clone());
+    let page_size = *use_memo(|_| page_size(), ());
+    let navigator = use_navigator().unwrap();
+    let asked = use_location()
+        .and_then(|location| location.query::<PageQuery>().ok())
+        .map_or(1, |query| query.page);
+    let page_count = postlist.len().div_ceil(page_size).max(1);
+    let page = asked.clamp(1, page_count);
+    // out of range pages land on the nearest one
+    use_effect_with_deps(
+        move |(asked, page, loaded)| {
+            if *loaded && asked != page {
+                let query = PageQuery { page: *page };
+                navigator.replace_with_query(&Route::Posts, &query).ok();
+            }
+        },
+        (asked, page, !postlist.is_empty()),
+    );
 
     let colors: Colors = use_context().unwrap();
-    use_title("ポスト");
-    gloo_utils::document().document_element().unwrap().set_scroll_top(0);
+    let title = if page > 1 {
+        format!("ポスト {}/{}", page, page_count)
+    } else {
+        "ポスト".to_owned()
+    };
+    let path = if page > 1 {
+        format!("{}?page={}", Route::Posts.to_path(), page)
+    } else {
+        Route::Posts.to_path()
+    };
+    use_head(Head::page(title, path));
+    use_scroll_ready(!postlist.is_empty(), page);
 
     // nav button
-    let page_count = page_count(&postlist);
     let style_nav_button = use_style!(
         "
       padding: 0.3em 0.2em;
+      min-width: 1.5em;
+      text-align: center;
+      text-decoration: none;
+      color: ${normal};
       border-top: solid ${underground} 0.2em;
       &:hover {
         border-top: solid ${normal} 0.2em;
@@ -148,43 +239,54 @@ pub fn posts() -> Html {
         normal = colors.normal,
         bg2 = colors.bg2,
     );
-
+    let style_current = use_style!(
+        "border-top: solid ${normal} 0.2em; color: ${bold};",
+        normal = colors.normal,
+        bold = colors.bold
+    );
     let style_disable = use_style!("pointer-events: none; color: ${shadow};", shadow = colors.shadow);
-    let link = |item: PageNumMod, page_num: yew::UseStateHandle<usize>| -> Html {
-        let disabled = match item {
-            PageNumMod::Next => *page_num + 1 >= page_count,
-            PageNumMod::Prev => *page_num == 0,
-        };
-        let mark = item.value();
 
-        let mut class = classes![style_nav_button.clone()];
+    let flip = |item: PageNumMod| -> Html {
+        let (to, disabled) = match item {
+            PageNumMod::Prev => (page.saturating_sub(1), page == 1),
+            PageNumMod::Next => (page + 1, page >= page_count),
+        };
         if disabled {
-            class.push(style_disable.clone());
-        }
-        let next = page_flip(*page_num, &item, page_count);
-        let onclick = Callback::from(move |_| page_num.set(next));
-        html! {
-            <button {class} {onclick}>
-                { mark }
-            </button>
+            let classes = classes![style_nav_button.clone(), style_disable.clone()];
+            return html! { <span class={classes} aria-disabled="true">{ item.value() }</span> };
         }
+        html! { <PageLink page={to} classes={style_nav_button.clone()}>{ item.value() }</PageLink> }
     };
+    let numbers = pager(page, page_count)
+        .into_iter()
+        .map(|n| match n {
+            Some(n) if n == page => html! {
+                <PageLink page={n} current=true classes={classes![style_nav_button.clone(), style_current.clone()]}>{ n }</PageLink>
+            },
+            Some(n) => html! { <PageLink page={n} classes={style_nav_button.clone()}>{ n }</PageLink> },
+            None => html! { <span>{ "…" }</span> },
+        })
+        .collect::<Html>();
 
     // List
     let style_nav = use_style!(
         "
             margin-top: 1em;
             margin-left: -0.5em;
-            display: grid;
-            grid-template-columns: repeat(2, 4em);
-            grid-gap: 1.5em;"
+            display: flex;
+            flex-wrap: wrap;
+            align-items: baseline;
+            gap: 0.8em;"
     );
 
-    let start = *page_num * 5;
+    if let Some((error, retry)) = failed {
+        return html! { <ErrorView {error} {retry} /> };
+    }
+    let start = (page - 1) * page_size;
     let postlist = postlist
         .iter()
         .skip(start)
-        .take(5)
+        .take(page_size)
         .cloned()
         .map(|post| {
             let key = post.url.clone();
@@ -192,12 +294,13 @@ pub fn posts() -> Html {
         })
         .collect::<Html>();
     html! {
-        <>
+        <div class="h-feed">
         { postlist }
-           <nav class={style_nav} style="float: right">
-                { link(PageNumMod::Prev, page_num.clone()) }
-                { link(PageNumMod::Next, page_num.clone()) }
+           <nav class={style_nav} style="float: right" aria-label="ページ">
+                { flip(PageNumMod::Prev) }
+                { numbers }
+                { flip(PageNumMod::Next) }
             </nav>
-        </>
+        </div>
     }
 }
@@ -0,0 +1,186 @@
+//! scroll position and focus across route changes
+//! New navigations go to the top or to `#fragment`, back and forward go where the reader
+//! left. Pages load their content in a suspense, so they tell the manager with
+//! `use_scroll_ready` when there is something to scroll to.
+use gloo_events::{EventListener, EventListenerOptions};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlElement, ScrollRestoration};
+use yew::{
+    function_component, hook, html, use_context, use_effect_with_deps, use_state, Children, ContextProvider, Html,
+    Properties,
+};
+use yew_router::hooks::use_location;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Top,
+    Fragment(String),
+    Restore(f64),
+}
+
+#[derive(Default)]
+struct State {
+    /// last scroll position by url
+    positions: HashMap<String, f64>,
+    /// set by `popstate`, the saved position of the entry we went back or forward to
+    popped: Option<Option<f64>>,
+    /// path and query of the shown page, and its fragment
+    page: Option<String>,
+    hash: String,
+    pending: Option<Target>,
+    /// no focus move on the first page
+    navigated: bool,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct ScrollManager(Rc<RefCell<State>>);
+
+impl PartialEq for ScrollManager {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// path, query and fragment of the current entry
+fn url() -> String {
+    let location = gloo_utils::window().location();
+    [
+        location.pathname().unwrap_or_default(),
+        location.search().unwrap_or_default(),
+        location.hash().unwrap_or_default(),
+    ]
+    .concat()
+}
+
+fn scroll_to(y: f64) {
+    gloo_utils::window().scroll_to_with_x_and_y(0.0, y);
+}
+
+/// heading of `<main>`, or `<main>` itself, for screen readers to start from
+/// focus scrolls, so it goes before the scroll target.
+fn focus_main() {
+    let document = gloo_utils::document();
+    let target = document
+        .query_selector("main h1")
+        .ok()
+        .flatten()
+        .or_else(|| document.query_selector("main").ok().flatten());
+    if let Some(target) = target.and_then(|t| t.dyn_into::<HtmlElement>().ok()) {
+        if !target.has_attribute("tabindex") {
+            target.set_attribute("tabindex", "-1").ok();
+        }
+        target.focus().ok();
+    }
+}
+
+impl ScrollManager {
+    /// the route changed, decide where to go
+    /// called while rendering, before the pages below get to `ready`.
+    fn navigate(&self, path: String, hash: String) {
+        let mut state = self.0.borrow_mut();
+        if state.page.as_deref() == Some(path.as_str()) && state.hash == hash {
+            return;
+        }
+        state.hash = hash.clone();
+        let popped = state.popped.take();
+        let same_page = state.page.as_deref() == Some(path.as_str());
+        state.page = Some(path);
+        if same_page {
+            // a fragment of the same page, the browser has scrolled to it
+            if let Some(Some(y)) = popped {
+                scroll_to(y);
+            }
+            return;
+        }
+        let fragment = hash.strip_prefix('#').filter(|id| !id.is_empty());
+        state.pending = Some(match (popped, fragment) {
+            (Some(Some(y)), _) => Target::Restore(y),
+            (_, Some(id)) => Target::Fragment(id.to_owned()),
+            _ => Target::Top,
+        });
+    }
+
+    /// the page has its content, go to the pending target
+    fn ready(&self) {
+        let mut state = self.0.borrow_mut();
+        let Some(target) = state.pending.take() else {
+            return;
+        };
+        if state.navigated {
+            focus_main();
+        }
+        state.navigated = true;
+        match target {
+            Target::Top => scroll_to(0.0),
+            Target::Restore(y) => scroll_to(y),
+            Target::Fragment(id) => match gloo_utils::document().get_element_by_id(&id) {
+                Some(element) => element.scroll_into_view(),
+                None => scroll_to(0.0),
+            },
+        }
+    }
+}
+
+#[derive(PartialEq, Properties)]
+pub(crate) struct ScrollProps {
+    pub children: Children,
+}
+
+/// goes inside the router, around the pages
+#[function_component(Scroll)]
+pub(crate) fn scroll(props: &ScrollProps) -> Html {
+    let manager = use_state(ScrollManager::default);
+    {
+        let manager = (*manager).clone();
+        use_effect_with_deps(
+            move |_| {
+                let window = gloo_utils::window();
+                if let Ok(history) = window.history() {
+                    history.set_scroll_restoration(ScrollRestoration::Manual).ok();
+                }
+                let saver = manager.clone();
+                let on_scroll = EventListener::new(&window, "scroll", move |_| {
+                    let y = gloo_utils::window().scroll_y().unwrap_or_default();
+                    saver.0.borrow_mut().positions.insert(url(), y);
+                });
+                // in the capture phase it runs before the router renders the entry
+                let options = EventListenerOptions::run_in_capture_phase();
+                let on_pop = EventListener::new_with_options(&window, "popstate", options, move |_| {
+                    let mut state = manager.0.borrow_mut();
+                    state.popped = Some(state.positions.get(&url()).copied());
+                });
+                move || drop((on_scroll, on_pop))
+            },
+            (),
+        );
+    }
+    if let Some(location) = use_location() {
+        manager.navigate(
+            [location.path(), location.query_str()].concat(),
+            location.hash().to_owned(),
+        );
+    }
+    html! {
+        <ContextProvider<ScrollManager> context={(*manager).clone()}>
+            { props.children.clone() }
+        </ContextProvider<ScrollManager>>
+    }
+}
+
+/// call with `ready` once the content of the page is rendered, `deps` tell when it changed
+#[hook]
+pub(crate) fn use_scroll_ready<D>(ready: bool, deps: D)
+where
+    D: PartialEq + 'static,
+{
+    let manager: Option<ScrollManager> = use_context();
+    use_effect_with_deps(
+        move |(ready, _)| {
+            if let (true, Some(manager)) = (*ready, manager) {
+                manager.ready();
+            }
+        },
+        (ready, deps),
+    );
+}
@@ -1,59 +1,52 @@
-#[derive(PartialEq, Clone, Debug)]
-pub(crate) struct Colors {
-    pub background: &'static str,
-    pub bg2: &'static str,
-    pub brand_bg1: &'static str,
-    pub brand_bg2: &'static str,
-    pub brand_fg1: &'static str,
-    pub brand_fg2: &'static str,
-    pub rev_bg: &'static str,
-    pub rev_fg: &'static str,
-    pub rev_shadow: &'static str,
-    pub bold: &'static str,
-    pub normal: &'static str,
-    pub shadow: &'static str,
-    pub underground: &'static str,
-    pub red: &'static str,
-    pub blue: &'static str,
-    pub red_bg: &'static str,
-    pub blue_bg: &'static str,
-    pub green_bg: &'static str,
-    pub colors_fg: &'static str,
+pub(crate) use fubuki_types::colors::{Colors, Palette, Scheme, Themes};
+use yew::Callback;
+
+/// `localStorage` keys of the chosen palette and its scheme
+/// `index.html` reads the scheme back before the app starts.
+const THEME_KEY: &str = "theme";
+const SCHEME_KEY: &str = "scheme";
+/// served copy of the palettes
+const THEMES_URL: &str = "/public/themes.yml";
+
+/// palette the reader picked, none to follow the system
+pub(crate) fn stored_theme() -> Option<String> {
+    let storage = gloo_utils::window().local_storage().ok()??;
+    storage.get_item(THEME_KEY).ok()?
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) enum Theme {
-    Light,
+pub(crate) fn store_theme(palette: &Palette) {
+    if let Ok(Some(storage)) = gloo_utils::window().local_storage() {
+        storage.set_item(THEME_KEY, &palette.name).ok();
+        storage.set_item(SCHEME_KEY, palette.scheme.name()).ok();
+    }
 }
 
-pub(crate) fn colors(theme: Theme) -> Colors {
-    match theme {
-        Theme::Light => Colors {
-            background: "#f3f3f3",
-            bg2: "#d4d4d4",
-            brand_bg1: "#d4d4d4",
-            brand_bg2: "#606060",
-            brand_fg1: "#f3f3f3",
-            brand_fg2: "#000000",
-            rev_bg: "#404040",
-            rev_fg: "#f3f3f3",
-            rev_shadow: "#505050",
-            bold: "#404040",
-            normal: "#505050",
-            shadow: "#d4d4d4",
-            underground: "#f1f1f1",
-            red: "#db4d6d",
-            blue: "#58b2dc",
-            // usubeni
-            red_bg: "#e87a90",
-            blue_bg: "#58b2dc",
-            // nae
-            green_bg: "#86C166",
-            colors_fg: "#f3f3f3",
-        },
+/// palettes as deployed, none when they can not be read
+pub(crate) async fn load_themes() -> Option<Themes> {
+    let text = gloo_net::http::Request::get(THEMES_URL)
+        .send()
+        .await
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+    match serde_yaml::from_str::<Themes>(&text) {
+        Ok(themes) if !themes.0.is_empty() => Some(themes),
+        Ok(_) => None,
+        Err(e) => {
+            log::error!("themes parser failed: {}", e);
+            None
+        }
     }
 }
 
+/// the palette the navbar toggle moves to, and how
+#[derive(Clone, PartialEq)]
+pub(crate) struct ThemeSwitch {
+    pub next: Palette,
+    pub set: Callback<Palette>,
+}
+
 pub struct Layout {
     pub nav_bar: f32,
     pub glitch: f32,
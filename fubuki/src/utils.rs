@@ -1,11 +1,16 @@
+use crate::cache;
+use fubuki_types::head::{Head, Tag};
+use gloo_net::http::Request;
+use std::fmt;
+use web_sys::Element;
 use yew::{
     hook,
     suspense::{use_future_with_deps, SuspensionResult},
-    use_effect, use_memo,
+    use_effect, use_effect_with_deps, use_memo, use_state, Callback,
 };
 
 #[hook]
-pub fn use_title<T: Into<String>>(title: T) {
+pub fn use_title(title: impl Into<String>) {
     let title = title.into();
     let pre_title = use_memo(|_| gloo_utils::document().title(), ());
 
@@ -17,38 +22,178 @@ pub fn use_title<T: Into<String>>(title: T) {
             }
         }
         move || {
-            gloo_utils::document().set_title(&*pre_title);
+            gloo_utils::document().set_title(&pre_title);
         }
     });
 }
 
+/// how to undo one tag
+enum Undo {
+    Remove(Element),
+    /// attribute or text before the change
+    Restore(Element, Option<&'static str>, Option<String>),
+}
+
+/// set one tag, reusing the element already in `<head>`
+fn set_tag(tag: &Tag) -> Option<Undo> {
+    let document = gloo_utils::document();
+    let (name, key, attribute, value) = match tag {
+        Tag::Name(name, content) => ("meta", Some(("name", *name)), Some("content"), content),
+        Tag::Property(property, content) => ("meta", Some(("property", *property)), Some("content"), content),
+        Tag::Link(rel, href) => ("link", Some(("rel", *rel)), Some("href"), href),
+        Tag::JsonLd(json) => ("script", None, None, json),
+    };
+    let set = |element: &Element| match attribute {
+        Some(attribute) => element.set_attribute(attribute, value).ok(),
+        None => {
+            element.set_text_content(Some(value));
+            Some(())
+        }
+    };
+    if let Some(element) = document.query_selector(&tag.selector()).ok()? {
+        let old = match attribute {
+            Some(attribute) => element.get_attribute(attribute),
+            None => element.text_content(),
+        };
+        set(&element)?;
+        return Some(Undo::Restore(element, attribute, old));
+    }
+    let element = document.create_element(name).ok()?;
+    match key {
+        Some((key, name)) => element.set_attribute(key, name).ok()?,
+        None => element.set_attribute("type", "application/ld+json").ok()?,
+    }
+    set(&element)?;
+    document.query_selector("head").ok()??.append_child(&element).ok()?;
+    Some(Undo::Remove(element))
+}
+
+/// title, description, canonical url, open graph and json-ld of the page
+/// index_gen writes the same tags into the static pages.
 #[hook]
-pub fn use_remote<T>(target: T) -> SuspensionResult<String>
+pub fn use_head(head: Head) {
+    use_title(head.title.clone());
+    use_effect_with_deps(
+        |head| {
+            let undo: Vec<Undo> = head.tags().iter().filter_map(set_tag).collect();
+            move || {
+                for undo in undo.into_iter().rev() {
+                    match undo {
+                        Undo::Remove(element) => element.remove(),
+                        Undo::Restore(element, Some(attribute), Some(old)) => {
+                            element.set_attribute(attribute, &old).ok();
+                        }
+                        Undo::Restore(element, Some(attribute), None) => {
+                            element.remove_attribute(attribute).ok();
+                        }
+                        Undo::Restore(element, None, old) => element.set_text_content(old.as_deref()),
+                    }
+                }
+            }
+        },
+        head,
+    );
+}
+
+/// posts per page when index_gen did not say
+const PAGE_SIZE: usize = 5;
+
+/// posts per page, from the `fubuki-page-size` meta index_gen writes
+pub fn page_size() -> usize {
+    gloo_utils::document()
+        .query_selector(r#"meta[name="fubuki-page-size"]"#)
+        .ok()
+        .flatten()
+        .and_then(|meta| meta.get_attribute("content"))
+        .and_then(|size| size.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(PAGE_SIZE)
+}
+
+/// site author from the `h-card` index_gen puts in the page, `(name, url)`
+pub fn site_author() -> Option<(String, String)> {
+    let card = gloo_utils::document().get_element_by_id("site-card")?;
+    let name = card.query_selector(".p-name").ok()??;
+    let url = name.get_attribute("href").unwrap_or_else(|| "/".to_owned());
+    Some((name.text_content()?, url))
+}
+
+/// why a fetch failed
+#[derive(Clone, Debug, PartialEq)]
+pub enum FetchError {
+    /// no such file, also when the host answers with the app shell instead
+    NotFound,
+    /// any other error status
+    Status(u16),
+    /// no answer, or the answer broke off
+    Network(String),
+    /// the answer is not what the page expects
+    Parse(String),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::NotFound => write!(f, "ページが見つかりません"),
+            FetchError::Status(status) => write!(f, "サーバーが {} を返しました", status),
+            FetchError::Network(e) => write!(f, "ネットワークエラー: {}", e),
+            FetchError::Parse(e) => write!(f, "読み込めない内容です: {}", e),
+        }
+    }
+}
+
+/// a fetched file, and a way to fetch it again
+pub struct Fetched<T> {
+    pub result: Result<T, FetchError>,
+    pub retry: Callback<()>,
+}
+
+pub(crate) async fn fetch(url: &str) -> Result<String, FetchError> {
+    let res = Request::get(url)
+        .send()
+        .await
+        .map_err(|e| FetchError::Network(e.to_string()))?;
+    match res.status() {
+        404 => return Err(FetchError::NotFound),
+        status if !res.ok() => return Err(FetchError::Status(status)),
+        _ => {}
+    }
+    // firebase rewrites unknown paths to the shell
+    let is_html = res
+        .headers()
+        .get("content-type")
+        .is_some_and(|kind| kind.starts_with("text/html"));
+    if is_html && !url.ends_with(".html") {
+        return Err(FetchError::NotFound);
+    }
+    res.text().await.map_err(|e| FetchError::Network(e.to_string()))
+}
+
+#[hook]
+pub fn use_remote<T>(target: T) -> SuspensionResult<Fetched<String>>
 where
     T: AsRef<str> + 'static + PartialEq,
 {
+    let attempt = use_state(|| 0_u32);
     let res = use_future_with_deps(
-        |target| async move {
-            gloo_net::http::Request::get((*target).as_ref())
-                .send()
-                .await?
-                .text()
-                .await
-        },
-        target,
+        |deps| async move { cache::get(deps.0.as_ref()).await },
+        (target, *attempt),
     )?;
-    match *res {
-        Ok(ref res) => Ok(res.to_owned()),
-        Err(ref e) => Ok(e.to_string()),
-    }
+    let retry = Callback::from(move |_| attempt.set(*attempt + 1));
+    Ok(Fetched {
+        result: (*res).clone(),
+        retry,
+    })
 }
 
+/// none while loading
 #[hook]
-pub fn use_json<T, J>(target: T) -> Option<J>
+pub fn use_json<T, J>(target: T) -> Option<Fetched<J>>
 where
     T: AsRef<str> + 'static + PartialEq,
     J: serde::de::DeserializeOwned + 'static,
 {
-    let text = use_remote(target).ok()?;
-    serde_yaml::from_str(&text).ok()
+    let Fetched { result, retry } = use_remote(target).ok()?;
+    let result = result.and_then(|text| serde_yaml::from_str(&text).map_err(|e| FetchError::Parse(e.to_string())));
+    Some(Fetched { result, retry })
 }
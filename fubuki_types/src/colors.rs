@@ -0,0 +1,144 @@
+//! the site palettes, shared by the client styles and the images index_gen draws
+//! They live in `public/themes.yml`, the client starts from the copy built in and
+//! loads the file at runtime.
+use serde::{Deserialize, Serialize};
+
+/// `public/themes.yml` when the client was built
+const BUILTIN: &str = include_str!("../../public/themes.yml");
+
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize, Serialize)]
+pub struct Colors {
+    /// behind the whole page
+    pub page_bg: String,
+    pub background: String,
+    pub bg2: String,
+    pub brand_bg1: String,
+    pub brand_bg2: String,
+    pub brand_fg1: String,
+    pub brand_fg2: String,
+    pub rev_bg: String,
+    pub rev_fg: String,
+    pub rev_shadow: String,
+    pub bold: String,
+    pub normal: String,
+    pub shadow: String,
+    pub underground: String,
+    pub red: String,
+    pub blue: String,
+    pub red_bg: String,
+    pub blue_bg: String,
+    pub green_bg: String,
+    pub colors_fg: String,
+    pub code_comment: String,
+    pub code_keyword: String,
+    pub code_string: String,
+    pub code_constant: String,
+    pub code_function: String,
+    pub code_type: String,
+}
+
+impl Colors {
+    /// color of a role by field name
+    pub fn role(&self, role: &str) -> Option<&str> {
+        Some(match role {
+            "page_bg" => &self.page_bg,
+            "background" => &self.background,
+            "bg2" => &self.bg2,
+            "brand_bg1" => &self.brand_bg1,
+            "brand_bg2" => &self.brand_bg2,
+            "brand_fg1" => &self.brand_fg1,
+            "brand_fg2" => &self.brand_fg2,
+            "rev_bg" => &self.rev_bg,
+            "rev_fg" => &self.rev_fg,
+            "rev_shadow" => &self.rev_shadow,
+            "bold" => &self.bold,
+            "normal" => &self.normal,
+            "shadow" => &self.shadow,
+            "underground" => &self.underground,
+            "red" => &self.red,
+            "blue" => &self.blue,
+            "red_bg" => &self.red_bg,
+            "blue_bg" => &self.blue_bg,
+            "green_bg" => &self.green_bg,
+            "colors_fg" => &self.colors_fg,
+            "code_comment" => &self.code_comment,
+            "code_keyword" => &self.code_keyword,
+            "code_string" => &self.code_string,
+            "code_constant" => &self.code_constant,
+            "code_function" => &self.code_function,
+            "code_type" => &self.code_type,
+            _ => return None,
+        })
+    }
+}
+
+/// light or dark, what the system preference and `color-scheme` speak of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    Light,
+    Dark,
+}
+
+impl Scheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Light => "light",
+            Scheme::Dark => "dark",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Palette {
+    /// kept in `localStorage` and `data-theme`
+    pub name: String,
+    pub scheme: Scheme,
+    pub colors: Colors,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Themes(pub Vec<Palette>);
+
+impl Themes {
+    pub fn builtin() -> Self {
+        serde_yaml::from_str(BUILTIN).expect("public/themes.yml is broken")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Palette> {
+        self.0.iter().find(|palette| palette.name == name)
+    }
+
+    /// first palette of `scheme`, or the first at all
+    pub fn preferred(&self, scheme: Scheme) -> &Palette {
+        self.0
+            .iter()
+            .find(|palette| palette.scheme == scheme)
+            .or_else(|| self.0.first())
+            .expect("no palette")
+    }
+
+    /// the one after `name`, round to the first
+    pub fn next(&self, name: &str) -> &Palette {
+        let at = self.0.iter().position(|palette| palette.name == name);
+        let next = at.map_or(0, |at| (at + 1) % self.0.len());
+        &self.0[next]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin() {
+        let themes = Themes::builtin();
+        assert_eq!(themes.preferred(Scheme::Light).name, "light");
+        assert_eq!(themes.preferred(Scheme::Dark).name, "dark");
+        assert_eq!(themes.next("dark").name, "high-contrast");
+        assert_eq!(themes.next("high-contrast").name, "light");
+        assert_eq!(themes.next("gone").name, "light");
+        assert_eq!(themes.get("light").unwrap().colors.role("normal"), Some("#505050"));
+    }
+}
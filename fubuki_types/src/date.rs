@@ -1,3 +1,17 @@
+//! the japanese era dates of front matter
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParseError;
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("date parse failed")
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 pub enum Genngo {
     Heisei(u32), // 1989 - 2019
     Reiwa(u32),  // 2019 - ima
@@ -25,9 +39,9 @@ pub struct Date {
 
 impl Date {
     /// from 平成31/4/29
-    pub fn from_japan_locale(locale: String) -> anyhow::Result<Date> {
+    pub fn from_japan_locale(locale: String) -> Result<Date, ParseError> {
         if let Some(genngo) = locale.get(..6) {
-            let date = locale.get(6..).ok_or_else(|| anyhow::Error::msg("parse failed"))?;
+            let date = locale.get(6..).ok_or(ParseError)?;
             let spines: Result<Vec<u32>, _> = date
                 .replace("元年", "1年")
                 .split(' ')
@@ -39,7 +53,7 @@ impl Date {
                 .filter(|s| !s.is_empty())
                 .map(|s| s.parse::<u32>())
                 .collect();
-            let mut spines = spines?;
+            let mut spines = spines.map_err(|_| ParseError)?;
             let mut push = spines.len();
             if push < 6 {
                 push = 6 - push;
@@ -48,7 +62,7 @@ impl Date {
             let genngo = match genngo {
                 "平成" => Genngo::Heisei(spines[0]),
                 "令和" => Genngo::Reiwa(spines[0]),
-                _ => unreachable!(),
+                _ => return Err(ParseError),
             };
             return Ok(Date {
                 year: genngo.get_ce_year(),
@@ -59,7 +73,7 @@ impl Date {
                 second: spines[5],
             });
         }
-        Err(anyhow::Error::msg("parse failed"))
+        Err(ParseError)
     }
 
     pub fn to_iso_string(&self) -> String {
@@ -70,7 +84,7 @@ impl Date {
     }
 }
 
-pub fn from_jp_to_iso(jp: String) -> anyhow::Result<String> {
+pub fn from_jp_to_iso(jp: String) -> Result<String, ParseError> {
     Date::from_japan_locale(jp).map(|x| x.to_iso_string())
 }
 
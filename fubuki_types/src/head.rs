@@ -0,0 +1,209 @@
+//! per page `<head>` tags, set by the client and written into static pages by index_gen
+use crate::markdown::escape;
+use serde_json::json;
+
+pub const SITE: &str = "https://blog.gyara.moe";
+pub const SITE_NAME: &str = "島風造船所";
+pub const DESCRIPTION: &str = "世界哟。原来余是爱着汝的！";
+
+/// where a post is read, the client route and the canonical link
+pub fn post_url(slug: &str) -> String {
+    format!("{}/post/{}", SITE, slug)
+}
+
+/// open graph image index_gen draws for a post
+pub fn og_image(slug: &str) -> String {
+    format!("{}/og/{}.png", SITE, slug)
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Article {
+    /// rfc 3339
+    pub published: String,
+    pub section: String,
+    pub tags: Vec<String>,
+    pub author: String,
+    pub author_url: String,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Head {
+    pub title: String,
+    pub description: String,
+    /// path, `/post/<id>`
+    pub path: String,
+    /// absolute url
+    pub image: Option<String>,
+    pub article: Option<Article>,
+}
+
+/// one tag, found again by its key when updated
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Tag {
+    /// `<meta name=".." content="..">`
+    Name(&'static str, String),
+    /// `<meta property=".." content="..">`
+    Property(&'static str, String),
+    /// `<link rel=".." href="..">`
+    Link(&'static str, String),
+    /// `<script type="application/ld+json">`
+    JsonLd(String),
+}
+
+impl Tag {
+    /// css selector of the element
+    pub fn selector(&self) -> String {
+        match self {
+            Tag::Name(name, _) => format!(r#"meta[name="{}"]"#, name),
+            Tag::Property(property, _) => format!(r#"meta[property="{}"]"#, property),
+            Tag::Link(rel, _) => format!(r#"link[rel="{}"]"#, rel),
+            Tag::JsonLd(_) => r#"script[type="application/ld+json"]"#.to_owned(),
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        match self {
+            Tag::Name(name, content) => format!(r#"<meta name="{}" content="{}" />"#, name, escape(content)),
+            Tag::Property(property, content) => {
+                format!(r#"<meta property="{}" content="{}" />"#, property, escape(content))
+            }
+            Tag::Link(rel, href) => format!(r#"<link rel="{}" href="{}" />"#, rel, escape(href)),
+            // `</` would end the script early
+            Tag::JsonLd(json) => format!(
+                r#"<script type="application/ld+json">{}</script>"#,
+                json.replace("</", "<\\/")
+            ),
+        }
+    }
+}
+
+impl Head {
+    /// a page that is not a post
+    pub fn page(title: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: DESCRIPTION.to_owned(),
+            path: path.into(),
+            image: None,
+            article: None,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}{}", SITE, self.path)
+    }
+
+    pub fn full_title(&self) -> String {
+        if self.title.is_empty() {
+            SITE_NAME.to_owned()
+        } else {
+            format!("{} - {}", SITE_NAME, self.title)
+        }
+    }
+
+    fn json_ld(&self, article: &Article) -> String {
+        let mut posting = json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": self.title,
+            "description": self.description,
+            "url": self.url(),
+            "mainEntityOfPage": self.url(),
+            "datePublished": article.published,
+            "articleSection": article.section,
+            "keywords": article.tags.join(", "),
+            "author": {
+                "@type": "Person",
+                "name": article.author,
+                "url": article.author_url,
+            },
+        });
+        if let Some(image) = &self.image {
+            posting["image"] = json!(image);
+        }
+        posting.to_string()
+    }
+
+    pub fn tags(&self) -> Vec<Tag> {
+        let title = if self.title.is_empty() {
+            SITE_NAME.to_owned()
+        } else {
+            self.title.clone()
+        };
+        let mut tags = vec![
+            Tag::Name("description", self.description.clone()),
+            Tag::Link("canonical", self.url()),
+            Tag::Property("og:site_name", SITE_NAME.to_owned()),
+            Tag::Property("og:title", title.clone()),
+            Tag::Property("og:description", self.description.clone()),
+            Tag::Property("og:url", self.url()),
+            Tag::Property(
+                "og:type",
+                if self.article.is_some() { "article" } else { "website" }.to_owned(),
+            ),
+            Tag::Name(
+                "twitter:card",
+                if self.image.is_some() {
+                    "summary_large_image"
+                } else {
+                    "summary"
+                }
+                .to_owned(),
+            ),
+            Tag::Name("twitter:title", title),
+            Tag::Name("twitter:description", self.description.clone()),
+        ];
+        if let Some(image) = &self.image {
+            tags.push(Tag::Property("og:image", image.clone()));
+            tags.push(Tag::Name("twitter:image", image.clone()));
+        }
+        if let Some(article) = &self.article {
+            tags.push(Tag::Property("article:published_time", article.published.clone()));
+            tags.push(Tag::Property("article:section", article.section.clone()));
+            tags.push(Tag::JsonLd(self.json_ld(article)));
+        }
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post() {
+        let head = Head {
+            title: "雪風".to_owned(),
+            description: "a \"b\"".to_owned(),
+            path: "/post/a".to_owned(),
+            image: None,
+            article: Some(Article {
+                published: "2021-04-01T00:00:00Z".to_owned(),
+                section: "Tech".to_owned(),
+                tags: vec!["rust".to_owned(), "yew".to_owned()],
+                author: "gyara".to_owned(),
+                author_url: "https://blog.gyara.moe/".to_owned(),
+            }),
+        };
+        let html: Vec<String> = head.tags().iter().map(Tag::to_html).collect();
+        assert!(html.contains(&r#"<meta name="description" content="a &quot;b&quot;" />"#.to_owned()));
+        assert!(html.contains(&r#"<link rel="canonical" href="https://blog.gyara.moe/post/a" />"#.to_owned()));
+        assert!(html.contains(&r#"<meta property="og:type" content="article" />"#.to_owned()));
+        let json = head
+            .tags()
+            .into_iter()
+            .find_map(|t| match t {
+                Tag::JsonLd(json) => Some(json),
+                _ => None,
+            })
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["@type"], "BlogPosting");
+        assert_eq!(json["datePublished"], "2021-04-01T00:00:00Z");
+        assert_eq!(json["keywords"], "rust, yew");
+        assert_eq!(
+            Tag::JsonLd("\"</script>\"".to_owned()).to_html(),
+            r#"<script type="application/ld+json">"<\/script>"</script>"#
+        );
+    }
+}
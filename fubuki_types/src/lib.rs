@@ -1,7 +1,13 @@
+pub mod colors;
+pub mod date;
+pub mod head;
+pub mod markdown;
+pub mod webmention;
+
 use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct FrontMatter {
     pub title: String,
     pub category: String,
@@ -10,6 +16,12 @@ pub struct FrontMatter {
     pub date: String,
     #[serde(default)]
     pub spoiler: Spoiler,
+    /// language of the post, when it differs from the site
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// open graph image, set by index_gen for images it drew
+    #[serde(default)]
+    pub image: Option<String>,
 }
 
 impl FrontMatter {
@@ -24,21 +36,21 @@ impl FrontMatter {
             category: self.category,
             tags: self.tags,
             spoiler: self.spoiler,
+            lang: self.lang,
+            image: self.image,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
 #[serde(untagged)]
 pub enum Spoiler {
+    #[default]
     None,
-    Some { target: String, level: u32 },
-}
-
-impl Default for Spoiler {
-    fn default() -> Self {
-        Self::None
-    }
+    Some {
+        target: String,
+        level: u32,
+    },
 }
 
 #[derive(PartialEq, Clone, Deserialize, Serialize, Debug, Default)]
@@ -51,6 +63,10 @@ pub struct Post {
     pub category: String,
     pub tags: Vec<String>,
     pub spoiler: Spoiler,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
 }
 
 impl Post {
@@ -97,6 +113,8 @@ mod tests {
             tags: vec!["tags".to_owned()],
             category: "category".to_owned(),
             spoiler: Spoiler::None,
+            lang: None,
+            image: None,
         };
         let mut post = fm.into_post("https://example.com".to_owned(), "".to_owned());
         post.remove_time();
@@ -112,6 +130,8 @@ mod tests {
             tags: vec!["tags".to_owned()],
             category: "category".to_owned(),
             spoiler: Spoiler::None,
+            lang: None,
+            image: None,
         };
         let mut post = fm.into_post("https://example.com".to_owned(), "".to_owned());
         post.remove_time();
@@ -0,0 +1,312 @@
+//! markdown rendering shared by fubuki and index_gen,
+//! so that the client and the feed always agree on the output.
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt};
+
+mod math;
+mod ruby;
+pub mod shortcode;
+pub mod tree;
+
+pub use math::{to_mathml, MathError};
+pub use shortcode::ShortcodeError;
+
+/// parser options used everywhere
+pub fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_OLD_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_SMART_PUNCTUATION
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_MATH
+}
+
+/// one entry of the table of contents
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize, Debug)]
+pub struct Heading {
+    pub level: u8,
+    pub id: String,
+    pub title: String,
+}
+
+/// where the html goes, embeds fall back to plain links in feeds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Web,
+    Feed,
+}
+
+/// things rendered as an error placeholder, index_gen turns them into build errors
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Error {
+    Math(MathError),
+    Shortcode(ShortcodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Math(e) => e.fmt(f),
+            Error::Shortcode(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// parsed markdown, ready to be rendered
+/// shortcodes work on the source, expand them with [`shortcode::expand`] first.
+pub struct Document<'a> {
+    pub events: Vec<Event<'a>>,
+    pub toc: Vec<Heading>,
+    pub errors: Vec<Error>,
+}
+
+impl<'a> Document<'a> {
+    pub fn parse(md: &'a str) -> Self {
+        let mut events: Vec<_> = Parser::new_ext(md, options()).into_offset_iter().collect();
+        ruby::restore_headings(md, &mut events);
+        let mut events = ruby::expand_ruby(events.into_iter().map(|(event, _)| event).collect());
+        let toc = anchor_headings(&mut events);
+        lazy_images(&mut events);
+        let mut errors = Vec::new();
+        math::render_math(&mut events, &mut errors);
+        Self { events, toc, errors }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, self.events.iter().cloned());
+        html
+    }
+
+    /// elements instead of html, raw html in it sanitized
+    pub fn to_tree(&self) -> Vec<tree::Node> {
+        tree::build(&self.events)
+    }
+}
+
+pub struct Rendered {
+    pub html: String,
+    pub toc: Vec<Heading>,
+    pub errors: Vec<Error>,
+}
+
+pub fn render_to(md: &str, target: Target) -> Rendered {
+    let (md, shortcode_errors) = shortcode::expand(md, target);
+    let doc = Document::parse(&md);
+    let mut errors: Vec<Error> = shortcode_errors.into_iter().map(Error::Shortcode).collect();
+    errors.extend(doc.errors.iter().cloned());
+    Rendered {
+        html: doc.to_html(),
+        toc: doc.toc,
+        errors,
+    }
+}
+
+/// render markdown to html, return the html and the table of contents
+pub fn render(md: &str) -> (String, Vec<Heading>) {
+    let Rendered { html, toc, .. } = render_to(md, Target::Web);
+    (html, toc)
+}
+
+/// render markdown for the web to a tree of elements, with the table of contents
+pub fn render_tree(md: &str) -> (Vec<tree::Node>, Vec<Heading>) {
+    let (md, _) = shortcode::expand(md, Target::Web);
+    let doc = Document::parse(&md);
+    (doc.to_tree(), doc.toc)
+}
+
+/// escape text for html body and attribute
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// heading text to id
+/// keep letters of every script (so CJK headings survive), turn spaces into `-`
+/// and drop the punctuation.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            slug.push(c);
+        } else if c.is_whitespace() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_owned()
+    } else {
+        slug.to_owned()
+    }
+}
+
+/// hand out ids, suffix `-1`, `-2` .. on collision
+#[derive(Default)]
+struct Slugs {
+    used: HashSet<String>,
+}
+
+impl Slugs {
+    fn unique(&mut self, base: String) -> String {
+        let mut id = base.clone();
+        let mut n = 0;
+        while self.used.contains(&id) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
+/// give every heading an id and collect them as toc
+/// ids written by hand with `{#id}` are kept.
+fn anchor_headings(events: &mut [Event]) -> Vec<Heading> {
+    let mut slugs = Slugs::default();
+    // reserve hand written ids first, generated ones should not steal them
+    for event in events.iter() {
+        if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+            slugs.used.insert(id.to_string());
+        }
+    }
+    let mut toc = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::Heading { .. }) = events[i] {
+            let mut title = String::new();
+            let mut end = i + 1;
+            while end < events.len() && !matches!(events[end], Event::End(TagEnd::Heading(_))) {
+                if let Event::Text(text) | Event::Code(text) = &events[end] {
+                    title.push_str(text);
+                }
+                end += 1;
+            }
+            if let Event::Start(Tag::Heading { level, id, .. }) = &mut events[i] {
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => {
+                        let slug = slugs.unique(slugify(&title));
+                        *id = Some(CowStr::from(slug.clone()));
+                        slug
+                    }
+                };
+                toc.push(Heading {
+                    level: *level as u8,
+                    id,
+                    title: title.trim().to_owned(),
+                });
+            }
+            i = end;
+        }
+        i += 1;
+    }
+    toc
+}
+
+/// markdown images load lazily, the alt text is flattened
+fn lazy_images(events: &mut Vec<Event>) {
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::Image { dest_url, title, .. }) = &events[i] {
+            let (src, title) = (escape(dest_url), escape(title));
+            let mut alt = String::new();
+            let mut end = i + 1;
+            while end < events.len() && !matches!(events[end], Event::End(TagEnd::Image)) {
+                if let Event::Text(text) | Event::Code(text) = &events[end] {
+                    alt.push_str(text);
+                }
+                end += 1;
+            }
+            let title = if title.is_empty() {
+                String::new()
+            } else {
+                format!(r#" title="{}""#, title)
+            };
+            let img = format!(r#"<img src="{}" alt="{}"{} loading="lazy">"#, src, escape(&alt), title);
+            let end = end.min(events.len() - 1);
+            events.splice(i..=end, [Event::InlineHtml(CowStr::from(img))]);
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slug() {
+        assert_eq!(slugify("Headings"), "Headings");
+        assert_eq!(slugify("Horizontal rule"), "Horizontal-rule");
+        assert_eq!(slugify("雪風と磯風"), "雪風と磯風");
+        assert_eq!(slugify(" What's  new? "), "Whats-new");
+        assert_eq!(slugify("!?"), "section");
+    }
+
+    #[test]
+    fn heading_id() {
+        let (html, toc) = render("# Lists\n\n## 順序\n\n## 順序\n\n# Code {#code}\n\n# code\n");
+        assert!(html.contains(r#"<h1 id="Lists">Lists</h1>"#));
+        assert!(html.contains(r#"<h2 id="順序-1">順序</h2>"#));
+        assert!(html.contains(r#"<h1 id="code">Code</h1>"#));
+        assert!(html.contains(r#"<h1 id="code-1">code</h1>"#));
+        let ids: Vec<&str> = toc.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, ["Lists", "順序", "順序-1", "code", "code-1"]);
+        assert_eq!(toc[1].level, 2);
+    }
+
+    #[test]
+    fn heading_id_suffix() {
+        let (_, toc) = render("# code\n\n# code-1\n\n# code\n");
+        let ids: Vec<&str> = toc.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, ["code", "code-1", "code-2"]);
+    }
+
+    #[test]
+    fn math() {
+        let doc = Document::parse("$x^2$ and\n\n$$\\sqrt{2}$$\n\n$\\nope$\n");
+        let html = doc.to_html();
+        assert!(html.contains(r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="inline">"#));
+        assert!(html.contains(r#"display="block""#));
+        assert!(html.contains(r#"<code class="math-error""#));
+        assert_eq!(doc.errors.len(), 1);
+    }
+
+    #[test]
+    fn lazy_image() {
+        let (html, _) = render("![雪 *風*](/a.png \"t\")\n");
+        assert!(html.contains(r#"<img src="/a.png" alt="雪 風" title="t" loading="lazy">"#));
+    }
+
+    #[test]
+    fn ruby() {
+        let (html, toc) = render("## Side {projects|オモチャ}\n\n[東京]{とう|きょう}\n\n`{a|b}`\n");
+        assert!(html.contains(
+            r#"<h2 id="Side-projects">Side <ruby>projects<rp>(</rp><rt>オモチャ</rt><rp>)</rp></ruby></h2>"#
+        ));
+        assert!(html.contains("<ruby>東<rp>(</rp><rt>とう</rt><rp>)</rp>京<rp>(</rp><rt>きょう</rt><rp>)</rp></ruby>"));
+        assert!(html.contains("<code>{a|b}</code>"));
+        assert_eq!(toc[0].title, "Side projects");
+        let (html, toc) = render("## {焔|ほむら} {#homura}\n\n## {焔|ほむら}\n");
+        assert!(html.contains(r#"<h2 id="homura"><ruby>焔"#));
+        assert!(html.contains(r#"<h2 id="焔"><ruby>焔"#));
+        assert_eq!(toc[1].title, "焔");
+        let (html, _) = render("### 一些其他的{链接|ゼルダ}\n");
+        assert!(html.contains("<h3 id=\"一些其他的链接\">一些其他的<ruby>链接"));
+    }
+}
@@ -0,0 +1,102 @@
+//! `$...$` and `$$...$$` to MathML
+//! index_gen bakes it into posts, pages it does not bake are converted here.
+use super::{escape, Error};
+use latex2mathml::{latex_to_mathml, token::Token, DisplayStyle};
+use pulldown_cmark::{CowStr, Event};
+use std::fmt;
+
+/// latex we can not turn into MathML
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MathError {
+    pub latex: String,
+    pub reason: String,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "math `{}`: {}", self.latex, self.reason)
+    }
+}
+
+impl std::error::Error for MathError {}
+
+/// commands latex2mathml does not know, it would render them as a parse error
+/// commands are read like its lexer does, a letter run or a single other char.
+fn unknown_command(latex: &str) -> Option<String> {
+    let mut chars = latex.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        let Some(first) = chars.next() else {
+            return Some(String::new());
+        };
+        let mut command = first.to_string();
+        while first.is_ascii_alphabetic() && chars.peek().is_some_and(char::is_ascii_alphabetic) {
+            command.extend(chars.next());
+        }
+        if let Token::Command(command) = Token::from_command(&command) {
+            return Some(command);
+        }
+    }
+    None
+}
+
+pub fn to_mathml(latex: &str, display: bool) -> Result<String, MathError> {
+    let style = if display {
+        DisplayStyle::Block
+    } else {
+        DisplayStyle::Inline
+    };
+    let error = |reason: String| MathError {
+        latex: latex.to_owned(),
+        reason,
+    };
+    if let Some(command) = unknown_command(latex) {
+        return Err(error(format!("unsupported command `\\{}`", command)));
+    }
+    latex_to_mathml(latex, style).map_err(|e| error(e.to_string()))
+}
+
+/// replace math events with MathML
+/// failed ones are shown as code, and reported in `errors`.
+pub(super) fn render_math(events: &mut [Event], errors: &mut Vec<Error>) {
+    for event in events.iter_mut() {
+        let (latex, display) = match event {
+            Event::InlineMath(latex) => (latex.to_string(), false),
+            Event::DisplayMath(latex) => (latex.to_string(), true),
+            _ => continue,
+        };
+        let html = match to_mathml(&latex, display) {
+            Ok(mathml) => mathml,
+            Err(e) => {
+                let html = format!(
+                    "<code class=\"math-error\" title=\"{}\">{}</code>",
+                    escape(&e.reason),
+                    escape(&latex)
+                );
+                errors.push(Error::Math(e));
+                html
+            }
+        };
+        *event = Event::InlineHtml(CowStr::from(html));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline() {
+        let mathml = to_mathml(r"\frac{a}{b}", false).unwrap();
+        assert!(mathml.contains(r#"display="inline""#));
+        assert!(mathml.contains("<mfrac><mi>a</mi><mi>b</mi></mfrac>"));
+    }
+
+    #[test]
+    fn unknown_command() {
+        let e = to_mathml(r"\foo x", true).unwrap_err();
+        assert_eq!(e.reason, r"unsupported command `\foo`");
+    }
+}
@@ -0,0 +1,221 @@
+//! ruby shorthand
+//! `{焔|ほむら}` or `[焔]{ほむら}` for the whole word,
+//! `{東京|とう|きょう}` or `[東京]{とう|きょう}` for a reading per character.
+use super::escape;
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use std::ops::Range;
+
+#[derive(PartialEq, Eq, Debug)]
+enum Piece<'t> {
+    Text(&'t str),
+    Ruby { base: &'t str, readings: Vec<&'t str> },
+}
+
+fn readings(inner: &str) -> Option<Vec<&str>> {
+    let readings: Vec<&str> = inner.split('|').collect();
+    if readings.iter().any(|r| r.trim().is_empty()) {
+        return None;
+    }
+    Some(readings)
+}
+
+/// `{base|reading}`, return the length eaten
+fn brace(s: &str) -> Option<(usize, Piece<'_>)> {
+    let end = s.find('}')?;
+    let inner = &s[1..end];
+    if inner.contains(['{', '\n']) {
+        return None;
+    }
+    let (base, rest) = inner.split_once('|')?;
+    if base.trim().is_empty() {
+        return None;
+    }
+    Some((
+        end + 1,
+        Piece::Ruby {
+            base,
+            readings: readings(rest)?,
+        },
+    ))
+}
+
+/// `[base]{reading}`, return the length eaten
+fn bracket(s: &str) -> Option<(usize, Piece<'_>)> {
+    let close = s.find(']')?;
+    let base = &s[1..close];
+    if base.trim().is_empty() || base.contains(['[', '\n']) {
+        return None;
+    }
+    let rest = s[close + 1..].strip_prefix('{')?;
+    let end = rest.find('}')?;
+    let inner = &rest[..end];
+    if inner.contains(['{', '\n']) {
+        return None;
+    }
+    Some((
+        close + 1 + 1 + end + 1,
+        Piece::Ruby {
+            base,
+            readings: readings(inner)?,
+        },
+    ))
+}
+
+fn split(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let found = match text.as_bytes()[i] {
+            b'{' => brace(&text[i..]),
+            b'[' => bracket(&text[i..]),
+            _ => None,
+        };
+        if let Some((len, ruby)) = found {
+            if last < i {
+                pieces.push(Piece::Text(&text[last..i]));
+            }
+            pieces.push(ruby);
+            i += len;
+            last = i;
+        } else {
+            i += 1;
+        }
+    }
+    if last < text.len() {
+        pieces.push(Piece::Text(&text[last..]));
+    }
+    pieces
+}
+
+fn rt(reading: &str) -> String {
+    format!("<rp>(</rp><rt>{}</rt><rp>)</rp>", escape(reading))
+}
+
+/// the base stays a text event, so toc titles and slugs only see the base
+fn ruby_events(base: &str, readings: &[&str], events: &mut Vec<Event>) {
+    events.push(Event::InlineHtml(CowStr::Borrowed("<ruby>")));
+    let chars: Vec<char> = base.chars().collect();
+    if readings.len() > 1 && readings.len() == chars.len() {
+        for (c, reading) in chars.into_iter().zip(readings) {
+            events.push(Event::Text(CowStr::from(c.to_string())));
+            events.push(Event::InlineHtml(CowStr::from(rt(reading))));
+        }
+    } else {
+        events.push(Event::Text(CowStr::from(base.to_owned())));
+        events.push(Event::InlineHtml(CowStr::from(rt(&readings.concat()))));
+    }
+    events.push(Event::InlineHtml(CowStr::Borrowed("</ruby>")));
+}
+
+fn expand_text(text: &str, events: &mut Vec<Event>) {
+    for piece in split(text) {
+        match piece {
+            Piece::Text(text) => events.push(Event::Text(CowStr::from(text.to_owned()))),
+            Piece::Ruby { base, readings } => ruby_events(base, &readings, events),
+        }
+    }
+}
+
+/// a trailing `{base|reading}` in a heading is parsed as heading attributes,
+/// put it back as the text it was, real attributes like `{#id}` are left alone
+pub(super) fn restore_headings<'a>(md: &'a str, events: &mut Vec<(Event<'a>, Range<usize>)>) {
+    let mut i = 0;
+    while i < events.len() {
+        let end = match &events[i].0 {
+            Event::Start(Tag::Heading { .. }) => {
+                i + events[i..]
+                    .iter()
+                    .position(|(event, _)| matches!(event, Event::End(TagEnd::Heading(_))))
+                    .unwrap_or(events.len() - i)
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let range = events[i].1.clone();
+        if let (Event::Start(Tag::Heading { id, classes, attrs, .. }), _) = &mut events[i] {
+            let ruby = id.is_none()
+                && classes.is_empty()
+                && !attrs.is_empty()
+                && attrs.iter().all(|(key, value)| value.is_none() && key.contains('|'));
+            let block = md[range.clone()].rfind('}').and_then(|close| {
+                let open = md[range.start..range.start + close].rfind('{')?;
+                Some((range.start + open, range.start + close + 1))
+            });
+            if let (true, Some((open, close))) = (ruby, block) {
+                attrs.clear();
+                // keep the space before the braces as written
+                let from = events[i + 1..end].last().map_or(open, |(_, inner)| inner.end.min(open));
+                events.insert(end, (Event::Text(CowStr::Borrowed(&md[from..close])), from..close));
+                i = end + 2;
+                continue;
+            }
+        }
+        i = end + 1;
+    }
+}
+
+/// expand ruby shorthand outside of code
+/// the parser splits text at `[` and `]`, so adjacent text is joined first.
+pub(super) fn expand_ruby(events: Vec<Event>) -> Vec<Event> {
+    let mut expanded = Vec::with_capacity(events.len());
+    let mut text = String::new();
+    let mut in_code = false;
+    for event in events {
+        match event {
+            Event::Text(t) if !in_code => {
+                text.push_str(&t);
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            _ => {}
+        }
+        if !text.is_empty() {
+            expand_text(&text, &mut expanded);
+            text.clear();
+        }
+        expanded.push(event);
+    }
+    if !text.is_empty() {
+        expand_text(&text, &mut expanded);
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorthand() {
+        assert_eq!(
+            split("我永远喜欢{焔|ほむら}！"),
+            vec![
+                Piece::Text("我永远喜欢"),
+                Piece::Ruby {
+                    base: "焔",
+                    readings: vec!["ほむら"]
+                },
+                Piece::Text("！"),
+            ]
+        );
+        assert_eq!(
+            split("[東京]{とう|きょう}"),
+            vec![Piece::Ruby {
+                base: "東京",
+                readings: vec!["とう", "きょう"]
+            }]
+        );
+    }
+
+    #[test]
+    fn not_ruby() {
+        assert_eq!(split("{a}"), vec![Piece::Text("{a}")]);
+        assert_eq!(split("{|a}"), vec![Piece::Text("{|a}")]);
+        assert_eq!(split("[a](b)"), vec![Piece::Text("[a](b)")]);
+        assert_eq!(split("{{< x >}}"), vec![Piece::Text("{{< x >}}")]);
+    }
+}
@@ -0,0 +1,429 @@
+//! shortcodes for embeds
+//! `{{< name positional key=value key="quoted value" >}}`,
+//! paired ones are closed by `{{< /name >}}`.
+//! Leave blank lines around paired shortcodes so the markdown inside is still parsed.
+use super::{escape, options, Target};
+use pulldown_cmark::{Event, Parser, Tag};
+use std::{fmt, ops::Range};
+
+/// a shortcode we can not expand
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ShortcodeError {
+    pub code: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ShortcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "shortcode `{}`: {}", self.code, self.reason)
+    }
+}
+
+impl std::error::Error for ShortcodeError {}
+
+#[derive(Default, Debug)]
+pub struct Args {
+    positional: Vec<String>,
+    named: Vec<(String, String)>,
+}
+
+impl Args {
+    pub fn parse(input: &str) -> Self {
+        let mut args = Args::default();
+        let mut chars = input.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let mut key = None;
+            let mut value = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                match c {
+                    '=' if key.is_none() => key = Some(std::mem::take(&mut value)),
+                    '"' => {
+                        for c in chars.by_ref() {
+                            if c == '"' {
+                                break;
+                            }
+                            value.push(c);
+                        }
+                    }
+                    c => value.push(c),
+                }
+            }
+            match key {
+                Some(key) => args.named.push((key, value)),
+                None => args.positional.push(value),
+            }
+        }
+        args
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.named.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn nth(&self, n: usize) -> Option<&str> {
+        self.positional.get(n).map(String::as_str)
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        match self.named.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.named.push((key.to_owned(), value)),
+        }
+    }
+
+    fn require(&self, key: &str) -> Result<&str, String> {
+        self.get(key).ok_or_else(|| format!("missing `{}`", key))
+    }
+}
+
+impl fmt::Display for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for value in self.positional.iter() {
+            write!(f, " \"{}\"", value)?;
+        }
+        for (key, value) in self.named.iter() {
+            write!(f, " {}=\"{}\"", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+type Render = fn(&Args, Target) -> Result<String, String>;
+
+pub struct Shortcode {
+    pub name: &'static str,
+    /// render the opening tag, or the whole thing for unpaired ones
+    render: Render,
+    /// closing tag for paired shortcodes
+    close: Option<fn(Target) -> String>,
+}
+
+/// every shortcode we know about
+pub const REGISTRY: &[Shortcode] = &[
+    Shortcode {
+        name: "iframe",
+        render: iframe,
+        close: None,
+    },
+    Shortcode {
+        name: "youtube",
+        render: youtube,
+        close: None,
+    },
+    Shortcode {
+        name: "figure",
+        render: figure,
+        close: None,
+    },
+    Shortcode {
+        name: "details",
+        render: details,
+        close: Some(details_close),
+    },
+];
+
+fn iframe(args: &Args, target: Target) -> Result<String, String> {
+    let src = escape(args.require("src")?);
+    let title = args.get("title").map(escape);
+    let height = css_length(args.get("height").unwrap_or("15em")).ok_or("`height` is not a css length")?;
+    Ok(match target {
+        Target::Web => format!(
+            r#"<iframe class="embed" src="{}" title="{}" style="height: {};" loading="lazy"></iframe>"#,
+            src,
+            title.unwrap_or_default(),
+            height,
+        ),
+        Target::Feed => format!(
+            r#"<p><a href="{}">{}</a></p>"#,
+            src,
+            title.unwrap_or_else(|| src.clone())
+        ),
+    })
+}
+
+/// a number and a unit, `15em`, `320px`, nothing else makes it into a style
+pub(super) fn css_length(value: &str) -> Option<&str> {
+    const UNITS: [&str; 12] = [
+        "px", "em", "rem", "ex", "ch", "vh", "vw", "vmin", "vmax", "%", "pt", "cm",
+    ];
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, unit) = value.split_at(split);
+    (number.parse::<f64>().is_ok() && UNITS.contains(&unit)).then_some(value)
+}
+
+fn youtube(args: &Args, target: Target) -> Result<String, String> {
+    let id = args.nth(0).or_else(|| args.get("id")).ok_or("missing video id")?;
+    let id = escape(id);
+    let title = escape(args.get("title").unwrap_or("YouTube"));
+    Ok(match target {
+        Target::Web => format!(
+            r#"<iframe class="embed youtube" src="https://www.youtube-nocookie.com/embed/{}" title="{}" allowfullscreen loading="lazy"></iframe>"#,
+            id, title
+        ),
+        Target::Feed => format!(
+            r#"<p><a href="https://www.youtube.com/watch?v={0}"><img src="https://i.ytimg.com/vi/{0}/hqdefault.jpg" alt="{1}"></a></p>"#,
+            id, title
+        ),
+    })
+}
+
+fn figure(args: &Args, target: Target) -> Result<String, String> {
+    let src = escape(args.require("src")?);
+    let alt = escape(args.get("alt").or_else(|| args.get("caption")).unwrap_or_default());
+    let loading = match target {
+        Target::Web => r#" loading="lazy""#,
+        Target::Feed => "",
+    };
+    let size = match (args.get("width"), args.get("height")) {
+        (Some(width), Some(height)) => format!(r#" width="{}" height="{}""#, escape(width), escape(height)),
+        _ => String::new(),
+    };
+    let mut img = format!(r#"<img src="{}" alt="{}"{}{}>"#, src, alt, size, loading);
+    // responsive variants, filled in by index_gen for local images
+    if let (Target::Web, Some(srcset)) = (target, args.get("srcset")) {
+        let sizes = escape(args.get("sizes").unwrap_or("100vw"));
+        let source = match args.get("webp") {
+            Some(webp) if !webp.is_empty() => format!(
+                r#"<source type="image/webp" srcset="{}" sizes="{}">"#,
+                escape(webp),
+                sizes
+            ),
+            _ => String::new(),
+        };
+        img = format!(
+            r#"<picture>{}<img src="{}" srcset="{}" sizes="{}" alt="{}"{}{}></picture>"#,
+            source,
+            src,
+            escape(srcset),
+            sizes,
+            alt,
+            size,
+            loading
+        );
+    }
+    if let Some(link) = args.get("link") {
+        img = format!(r#"<a href="{}">{}</a>"#, escape(link), img);
+    }
+    let caption = args
+        .get("caption")
+        .map(|c| format!("<figcaption>{}</figcaption>", escape(c)))
+        .unwrap_or_default();
+    Ok(format!("<figure>{}{}</figure>", img, caption))
+}
+
+fn details(args: &Args, target: Target) -> Result<String, String> {
+    let summary = escape(args.get("summary").or_else(|| args.nth(0)).unwrap_or("詳細"));
+    Ok(match target {
+        Target::Web => format!("<details><summary>{}</summary>", summary),
+        // feed readers drop the toggle, show it expanded
+        Target::Feed => format!("<div><p><strong>{}</strong></p>", summary),
+    })
+}
+
+fn details_close(target: Target) -> String {
+    match target {
+        Target::Web => "</details>".to_owned(),
+        Target::Feed => "</div>".to_owned(),
+    }
+}
+
+fn lookup(name: &str) -> Option<&'static Shortcode> {
+    REGISTRY.iter().find(|s| s.name == name)
+}
+
+/// code spans and code blocks, shortcodes there are left as they are
+fn code_ranges(md: &str) -> Vec<Range<usize>> {
+    Parser::new_ext(md, options())
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Code(_) | Event::Start(Tag::CodeBlock(_))))
+        .map(|(_, range)| range)
+        .collect()
+}
+
+/// one shortcode in the source
+#[derive(Debug)]
+pub struct Call<'a> {
+    pub range: Range<usize>,
+    pub raw: &'a str,
+    pub name: &'a str,
+    /// `{{< /name >}}`
+    pub closing: bool,
+    pub args: Args,
+}
+
+impl Call<'_> {
+    /// write the shortcode back, with the args as they are now
+    pub fn to_source(&self) -> String {
+        if self.closing {
+            format!("{{{{< /{} >}}}}", self.name)
+        } else {
+            format!("{{{{< {}{} >}}}}", self.name, self.args)
+        }
+    }
+}
+
+/// every shortcode outside of code
+pub fn find(md: &str) -> Vec<Call<'_>> {
+    let code = code_ranges(md);
+    let mut calls = Vec::new();
+    let mut from = 0;
+    while let Some(start) = md[from..].find("{{<").map(|i| i + from) {
+        let end = match md[start..].find(">}}") {
+            Some(end) => start + end + 3,
+            None => break,
+        };
+        from = end;
+        if code.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let raw = &md[start..end];
+        let inner = raw[3..raw.len() - 3].trim();
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(inner) => (true, inner.trim()),
+            None => (false, inner),
+        };
+        let (name, args) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        calls.push(Call {
+            range: start..end,
+            raw,
+            name,
+            closing,
+            args: Args::parse(args),
+        });
+    }
+    calls
+}
+
+/// expand every shortcode into html
+/// broken ones are kept as code and reported.
+pub fn expand(md: &str, target: Target) -> (String, Vec<ShortcodeError>) {
+    let mut expanded = String::with_capacity(md.len());
+    let mut errors = Vec::new();
+    let mut open: Vec<(&'static Shortcode, String)> = Vec::new();
+    let mut last = 0;
+    for call in find(md) {
+        let error = |reason: String| ShortcodeError {
+            code: call.raw.to_owned(),
+            reason,
+        };
+        let html = if call.closing {
+            match open.pop() {
+                Some((shortcode, _)) if shortcode.name == call.name => Ok(shortcode.close.unwrap()(target)),
+                Some((shortcode, opened)) => {
+                    open.push((shortcode, opened));
+                    Err(error(format!("expect `{{{{< /{} >}}}}`", shortcode.name)))
+                }
+                None => Err(error("nothing to close".to_owned())),
+            }
+        } else {
+            match lookup(call.name) {
+                Some(shortcode) => {
+                    let html = (shortcode.render)(&call.args, target).map_err(error);
+                    if html.is_ok() && shortcode.close.is_some() {
+                        open.push((shortcode, call.raw.to_owned()));
+                    }
+                    html
+                }
+                None => Err(error("unknown shortcode".to_owned())),
+            }
+        };
+        expanded.push_str(&md[last..call.range.start]);
+        match html {
+            Ok(html) => expanded.push_str(&html),
+            Err(e) => {
+                expanded.push_str(&format!(
+                    "<code class=\"shortcode-error\" title=\"{}\">{}</code>",
+                    escape(&e.reason),
+                    escape(call.raw)
+                ));
+                errors.push(e);
+            }
+        }
+        last = call.range.end;
+    }
+    expanded.push_str(&md[last..]);
+    for (shortcode, raw) in open.into_iter().rev() {
+        expanded.push_str(&shortcode.close.unwrap()(target));
+        errors.push(ShortcodeError {
+            code: raw,
+            reason: "not closed".to_owned(),
+        });
+    }
+    (expanded, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let args = Args::parse(r#" abc src="https://a.b/c?d=e" height=15em title="schulze method" "#);
+        assert_eq!(args.nth(0), Some("abc"));
+        assert_eq!(args.get("src"), Some("https://a.b/c?d=e"));
+        assert_eq!(args.get("height"), Some("15em"));
+        assert_eq!(args.get("title"), Some("schulze method"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let md = r#"a {{< figure src=cover.webp caption="雪 風" >}} b {{< /details >}}"#;
+        let calls = find(md);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[0].to_source(),
+            r#"{{< figure src="cover.webp" caption="雪 風" >}}"#
+        );
+        assert!(calls[1].closing);
+        assert_eq!(calls[1].name, "details");
+    }
+
+    #[test]
+    fn iframe() {
+        let md = r#"{{< iframe src="https://schulze.fubuki.me" height=15em >}}"#;
+        let (web, errors) = expand(md, Target::Web);
+        assert!(errors.is_empty());
+        assert!(web.starts_with(r#"<iframe class="embed" src="https://schulze.fubuki.me""#));
+        assert!(web.contains(r#"style="height: 15em;""#));
+        let (web, _) = expand(r#"{{< iframe src="https://a.b" height=37.5% >}}"#, Target::Web);
+        assert!(web.contains(r#"style="height: 37.5%;""#));
+        let (feed, _) = expand(md, Target::Feed);
+        assert_eq!(
+            feed,
+            r#"<p><a href="https://schulze.fubuki.me">https://schulze.fubuki.me</a></p>"#
+        );
+    }
+
+    #[test]
+    fn details() {
+        let md = "{{< details summary=\"ネタバレ\" >}}\n\nhidden\n\n{{< /details >}}\n";
+        let (web, errors) = expand(md, Target::Web);
+        assert!(errors.is_empty());
+        assert_eq!(web, "<details><summary>ネタバレ</summary>\n\nhidden\n\n</details>\n");
+    }
+
+    #[test]
+    fn broken() {
+        let (_, errors) = expand("{{< nope >}} {{< details >}}", Target::Web);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].reason, "unknown shortcode");
+        assert_eq!(errors[1].reason, "not closed");
+        let (_, errors) = expand("{{< iframe >}}", Target::Web);
+        assert_eq!(errors[0].reason, "missing `src`");
+        for height in ["15", "em", "1.2.3em", "10em;color:red", "-1em", "calc(1em)"] {
+            let md = format!(r#"{{{{< iframe src="https://a.b" height="{}" >}}}}"#, height);
+            let (_, errors) = expand(&md, Target::Web);
+            assert_eq!(errors[0].reason, "`height` is not a css length");
+        }
+    }
+
+    #[test]
+    fn in_code() {
+        let md = "`{{< nope >}}`\n\n```\n{{< nope >}}\n```\n";
+        assert_eq!(expand(md, Target::Web), (md.to_owned(), Vec::new()));
+    }
+}
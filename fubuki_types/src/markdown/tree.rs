@@ -0,0 +1,916 @@
+//! markdown as a tree of elements, for the client to build its own nodes from
+//! Raw html, written in posts or generated by the passes before, goes through an allowlist on
+//! the way: unknown elements are unwrapped, scripts, styles and forms go with their content,
+//! event handlers, script urls and inline styles are dropped.
+use super::{escape, shortcode};
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag, TagEnd};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub name: &'static str,
+    pub attributes: Vec<(&'static str, String)>,
+    pub children: Vec<Node>,
+}
+
+const ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "bdi",
+    "bdo",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "data",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "iframe",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "picture",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "ruby",
+    "s",
+    "samp",
+    "small",
+    "source",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+/// what latex2mathml writes, and a bit more
+const MATHML: &[&str] = &[
+    "math",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "mrow",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mstyle",
+    "merror",
+    "mpadded",
+    "mphantom",
+    "mfenced",
+    "menclose",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mmultiscripts",
+    "mprescripts",
+    "none",
+    "mtable",
+    "mtr",
+    "mtd",
+    "semantics",
+    "annotation",
+];
+
+/// gone together with what they hold
+const DROPPED: &[&str] = &[
+    "script", "style", "template", "noscript", "textarea", "select", "button", "form", "object", "embed", "svg",
+    "title", "head",
+];
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+const GLOBAL_ATTRIBUTES: &[&str] = &["id", "class", "title", "lang", "dir"];
+
+const ELEMENT_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "name", "rel"]),
+    (
+        "img",
+        &[
+            "src", "alt", "width", "height", "loading", "decoding", "srcset", "sizes",
+        ],
+    ),
+    ("source", &["src", "srcset", "sizes", "type", "media"]),
+    (
+        "iframe",
+        &["src", "allowfullscreen", "loading", "allow", "referrerpolicy", "style"],
+    ),
+    ("td", &["colspan", "rowspan", "align"]),
+    ("th", &["colspan", "rowspan", "align", "scope"]),
+    ("col", &["span"]),
+    ("colgroup", &["span"]),
+    ("ol", &["start", "reversed", "type"]),
+    ("li", &["value"]),
+    ("time", &["datetime"]),
+    ("data", &["value"]),
+    ("details", &["open"]),
+    ("q", &["cite"]),
+    ("blockquote", &["cite"]),
+    ("del", &["cite", "datetime"]),
+    ("ins", &["cite", "datetime"]),
+];
+
+const MATHML_ATTRIBUTES: &[&str] = &[
+    "xmlns",
+    "display",
+    "mathvariant",
+    "stretchy",
+    "fence",
+    "separator",
+    "separators",
+    "accent",
+    "accentunder",
+    "lspace",
+    "rspace",
+    "columnalign",
+    "rowalign",
+    "width",
+    "height",
+    "depth",
+    "linethickness",
+    "scriptlevel",
+    "displaystyle",
+    "movablelimits",
+    "largeop",
+    "symmetric",
+    "maxsize",
+    "minsize",
+    "notation",
+    "open",
+    "close",
+    "encoding",
+];
+
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite"];
+
+fn known(list: &[&'static str], name: &str) -> Option<&'static str> {
+    list.iter().find(|known| **known == name).copied()
+}
+
+/// relative, http, https or mailto
+fn safe_url(url: &str) -> bool {
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match url.find(':') {
+        Some(colon) if !url[..colon].contains(['/', '?', '#']) => {
+            matches!(&url[..colon], "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+fn safe_srcset(srcset: &str) -> bool {
+    srcset
+        .split(',')
+        .all(|candidate| safe_url(candidate.split_whitespace().next().unwrap_or_default()))
+}
+
+/// only the height the iframe shortcode gives
+fn safe_style(style: &str) -> bool {
+    style
+        .strip_prefix("height: ")
+        .and_then(|height| height.strip_suffix(';'))
+        .and_then(shortcode::css_length)
+        .is_some()
+}
+
+fn sanitize(element: &str, attributes: Vec<(String, String)>) -> Vec<(&'static str, String)> {
+    let allowed = ELEMENT_ATTRIBUTES
+        .iter()
+        .find(|(name, _)| *name == element)
+        .map_or(&[][..], |(_, allowed)| *allowed);
+    let mathml = MATHML.contains(&element);
+    attributes
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let name = known(GLOBAL_ATTRIBUTES, &name)
+                .or_else(|| known(allowed, &name))
+                .or_else(|| if mathml { known(MATHML_ATTRIBUTES, &name) } else { None })?;
+            let safe = match name {
+                "srcset" => safe_srcset(&value),
+                "style" => safe_style(&value),
+                name if URL_ATTRIBUTES.contains(&name) => safe_url(&value),
+                _ => true,
+            };
+            safe.then_some((name, value))
+        })
+        .collect()
+}
+
+/// `&amp;`, `&#39;` and the like in raw html
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+    Text(String),
+}
+
+/// `<name a="b" c=d e>` from just after `<`, none when the tag does not end here
+fn start_tag(input: &str) -> Option<(Token, usize)> {
+    let name_end = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == ':'))
+        .unwrap_or(input.len());
+    let name = input[..name_end].to_ascii_lowercase();
+    let mut attributes = Vec::new();
+    let mut at = name_end;
+    loop {
+        let rest = &input[at..];
+        let trimmed = rest.trim_start();
+        at += rest.len() - trimmed.len();
+        if trimmed.starts_with('>') {
+            let token = Token::Start {
+                name,
+                attributes,
+                self_closing: false,
+            };
+            return Some((token, at + 1));
+        }
+        if trimmed.starts_with("/>") {
+            let token = Token::Start {
+                name,
+                attributes,
+                self_closing: true,
+            };
+            return Some((token, at + 2));
+        }
+        if trimmed.is_empty() {
+            return None;
+        }
+        if trimmed.starts_with('/') {
+            at += 1;
+            continue;
+        }
+        let key_end = trimmed
+            .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(trimmed.len());
+        let key = trimmed[..key_end].to_ascii_lowercase();
+        at += key_end;
+        let rest = input[at..].trim_start();
+        let value = if let Some(rest) = rest.strip_prefix('=') {
+            let value = rest.trim_start();
+            at = input.len() - value.len();
+            match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = value[1..].find(quote)?;
+                    at += end + 2;
+                    decode(&value[1..end + 1])
+                }
+                _ => {
+                    let end = value
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(value.len());
+                    at += end;
+                    decode(&value[..end])
+                }
+            }
+        } else {
+            String::new()
+        };
+        attributes.push((key, value));
+    }
+}
+
+/// tokens of raw html, and a tag left open at the end
+fn tokenize(html: &str) -> (Vec<Token>, &str) {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = html;
+    let flush = |text: &mut String, tokens: &mut Vec<Token>| {
+        if !text.is_empty() {
+            tokens.push(Token::Text(decode(text)));
+            text.clear();
+        }
+    };
+    while let Some(lt) = rest.find('<') {
+        text.push_str(&rest[..lt]);
+        rest = &rest[lt..];
+        let after = &rest[1..];
+        if let Some(comment) = after.strip_prefix("!--") {
+            flush(&mut text, &mut tokens);
+            let Some(end) = comment.find("-->") else {
+                return (tokens, rest);
+            };
+            rest = &comment[end + 3..];
+        } else if after.starts_with(['!', '?']) {
+            flush(&mut text, &mut tokens);
+            let Some(end) = after.find('>') else {
+                return (tokens, rest);
+            };
+            rest = &after[end + 1..];
+        } else if let Some(end_tag) = after.strip_prefix('/') {
+            flush(&mut text, &mut tokens);
+            let Some(end) = end_tag.find('>') else {
+                return (tokens, rest);
+            };
+            let name = end_tag[..end].trim().to_ascii_lowercase();
+            tokens.push(Token::End(name));
+            rest = &end_tag[end + 1..];
+        } else if after.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            flush(&mut text, &mut tokens);
+            let Some((token, len)) = start_tag(after) else {
+                return (tokens, rest);
+            };
+            tokens.push(token);
+            rest = &after[len..];
+        } else {
+            text.push('<');
+            rest = after;
+        }
+    }
+    text.push_str(rest);
+    flush(&mut text, &mut tokens);
+    (tokens, "")
+}
+
+impl Element {
+    fn new(name: &'static str, attributes: Vec<(&'static str, String)>) -> Self {
+        Self {
+            name,
+            attributes,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// text of all the children
+    pub fn text(&self) -> String {
+        self.children.iter().map(Node::text).collect()
+    }
+
+    pub fn to_html(&self) -> String {
+        let attributes: String = self
+            .attributes
+            .iter()
+            .map(|(key, value)| format!(r#" {}="{}""#, key, escape(value)))
+            .collect();
+        if VOID.contains(&self.name) {
+            return format!("<{}{}>", self.name, attributes);
+        }
+        let children: String = self.children.iter().map(Node::to_html).collect();
+        format!("<{0}{1}>{2}</{0}>", self.name, attributes, children)
+    }
+}
+
+impl Node {
+    pub fn text(&self) -> String {
+        match self {
+            Node::Element(element) => element.text(),
+            Node::Text(text) => text.clone(),
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        match self {
+            Node::Element(element) => element.to_html(),
+            Node::Text(text) => escape(text),
+        }
+    }
+}
+
+struct Open {
+    element: Element,
+    /// opened by raw html, closed by its end tag or with the markdown around it
+    raw: bool,
+}
+
+#[derive(Default)]
+struct Builder {
+    root: Vec<Node>,
+    open: Vec<Open>,
+    /// elements each open markdown tag opened
+    frames: Vec<usize>,
+    /// inside a dropped element, its name and how deep
+    skip: Option<(String, usize)>,
+    /// a tag cut between two raw html events
+    partial: String,
+    alignments: Vec<Alignment>,
+    cell: usize,
+    in_head: bool,
+    in_body: bool,
+    footnotes: HashMap<String, usize>,
+}
+
+fn heading(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "h1",
+        HeadingLevel::H2 => "h2",
+        HeadingLevel::H3 => "h3",
+        HeadingLevel::H4 => "h4",
+        HeadingLevel::H5 => "h5",
+        HeadingLevel::H6 => "h6",
+    }
+}
+
+impl Builder {
+    fn push(&mut self, node: Node) {
+        let children = match self.open.last_mut() {
+            Some(open) => &mut open.element.children,
+            None => &mut self.root,
+        };
+        match (children.last_mut(), node) {
+            (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+            (_, node) => children.push(node),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        // line ends between top level blocks
+        let between = self.open.is_empty() && text.trim().is_empty();
+        if self.skip.is_none() && !text.is_empty() && !between {
+            self.push(Node::Text(text.to_owned()));
+        }
+    }
+
+    fn open(&mut self, name: &'static str, attributes: Vec<(&'static str, String)>, raw: bool) {
+        self.open.push(Open {
+            element: Element::new(name, attributes),
+            raw,
+        });
+    }
+
+    fn close(&mut self) {
+        if let Some(Open { mut element, .. }) = self.open.pop() {
+            // markdown images hold their alt text
+            if element.name == "img" && !element.children.is_empty() {
+                let alt = element.text();
+                element.children.clear();
+                element.attributes.push(("alt", alt));
+            }
+            self.push(Node::Element(element));
+        }
+    }
+
+    /// a footnote number, in order of first appearance
+    fn footnote(&mut self, label: &str) -> usize {
+        let next = self.footnotes.len() + 1;
+        *self.footnotes.entry(label.to_owned()).or_insert(next)
+    }
+
+    fn start(&mut self, tag: &Tag) {
+        // the body of a table opens with its first row
+        if matches!(tag, Tag::TableRow) && !self.in_body {
+            self.in_body = true;
+            self.open("tbody", Vec::new(), false);
+            if let Some(frame) = self.frames.last_mut() {
+                *frame += 1;
+            }
+        }
+        let before = self.open.len();
+        match tag {
+            Tag::Paragraph => self.open("p", Vec::new(), false),
+            Tag::Heading { level, id, classes, .. } => {
+                let mut attributes = Vec::new();
+                if let Some(id) = id {
+                    attributes.push(("id", id.to_string()));
+                }
+                if !classes.is_empty() {
+                    attributes.push(("class", classes.join(" ")));
+                }
+                self.open(heading(*level), attributes, false);
+            }
+            Tag::BlockQuote(_) => self.open("blockquote", Vec::new(), false),
+            Tag::CodeBlock(kind) => {
+                self.open("pre", Vec::new(), false);
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default(),
+                    CodeBlockKind::Indented => "",
+                };
+                let attributes = if lang.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![("class", format!("language-{}", lang))]
+                };
+                self.open("code", attributes, false);
+            }
+            Tag::HtmlBlock | Tag::MetadataBlock(_) => {}
+            Tag::List(Some(start)) if *start != 1 => self.open("ol", vec![("start", start.to_string())], false),
+            Tag::List(Some(_)) => self.open("ol", Vec::new(), false),
+            Tag::List(None) => self.open("ul", Vec::new(), false),
+            Tag::Item => self.open("li", Vec::new(), false),
+            Tag::FootnoteDefinition(label) => {
+                let n = self.footnote(label);
+                let attributes = vec![("class", "footnote-definition".to_owned()), ("id", label.to_string())];
+                self.open("div", attributes, false);
+                let mut number = Element::new("sup", vec![("class", "footnote-definition-label".to_owned())]);
+                number.children.push(Node::Text(n.to_string()));
+                self.push(Node::Element(number));
+            }
+            Tag::DefinitionList => self.open("dl", Vec::new(), false),
+            Tag::DefinitionListTitle => self.open("dt", Vec::new(), false),
+            Tag::DefinitionListDefinition => self.open("dd", Vec::new(), false),
+            Tag::Table(alignments) => {
+                self.alignments = alignments.clone();
+                self.in_body = false;
+                self.open("table", Vec::new(), false);
+            }
+            Tag::TableHead => {
+                self.in_head = true;
+                self.cell = 0;
+                self.open("thead", Vec::new(), false);
+                self.open("tr", Vec::new(), false);
+            }
+            Tag::TableRow => {
+                self.cell = 0;
+                self.open("tr", Vec::new(), false);
+            }
+            Tag::TableCell => {
+                let align = match self.alignments.get(self.cell) {
+                    Some(Alignment::Left) => Some("left"),
+                    Some(Alignment::Center) => Some("center"),
+                    Some(Alignment::Right) => Some("right"),
+                    _ => None,
+                };
+                let attributes = align
+                    .map(|align| vec![("style", format!("text-align: {}", align))])
+                    .unwrap_or_default();
+                self.cell += 1;
+                self.open(if self.in_head { "th" } else { "td" }, attributes, false);
+            }
+            Tag::Emphasis => self.open("em", Vec::new(), false),
+            Tag::Strong => self.open("strong", Vec::new(), false),
+            Tag::Strikethrough => self.open("del", Vec::new(), false),
+            Tag::Superscript => self.open("sup", Vec::new(), false),
+            Tag::Subscript => self.open("sub", Vec::new(), false),
+            Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                ..
+            } => {
+                let href = match link_type {
+                    LinkType::Email => format!("mailto:{}", dest_url),
+                    _ => dest_url.to_string(),
+                };
+                let mut attributes = Vec::new();
+                if safe_url(&href) {
+                    attributes.push(("href", href));
+                }
+                if !title.is_empty() {
+                    attributes.push(("title", title.to_string()));
+                }
+                self.open("a", attributes, false);
+            }
+            Tag::Image { dest_url, title, .. } => {
+                let mut attributes = vec![("loading", "lazy".to_owned())];
+                if safe_url(dest_url) {
+                    attributes.push(("src", dest_url.to_string()));
+                }
+                if !title.is_empty() {
+                    attributes.push(("title", title.to_string()));
+                }
+                self.open("img", attributes, false);
+            }
+        }
+        self.frames.push(self.open.len() - before);
+    }
+
+    fn end(&mut self, tag: &TagEnd) {
+        match tag {
+            TagEnd::TableHead => self.in_head = false,
+            TagEnd::Table => self.in_body = false,
+            _ => {}
+        }
+        let count = self.frames.pop().unwrap_or_default();
+        if count == 0 {
+            // raw html blocks may open what later markdown goes into, as `<details>`
+            return;
+        }
+        self.skip = None;
+        while self.open.last().is_some_and(|open| open.raw) {
+            self.close();
+        }
+        for _ in 0..count {
+            self.close();
+        }
+    }
+
+    fn raw(&mut self, html: &str) {
+        let source = std::mem::take(&mut self.partial) + html;
+        let (tokens, rest) = tokenize(&source);
+        self.partial = rest.to_owned();
+        for token in tokens {
+            if let Some((name, depth)) = &mut self.skip {
+                match token {
+                    Token::Start {
+                        name: start,
+                        self_closing: false,
+                        ..
+                    } if start == *name => *depth += 1,
+                    Token::End(end) if end == *name => match depth.checked_sub(1) {
+                        Some(outer) => *depth = outer,
+                        None => self.skip = None,
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+            match token {
+                Token::Start {
+                    name,
+                    attributes,
+                    self_closing,
+                } => self.raw_start(name, attributes, self_closing),
+                Token::End(name) => self.raw_end(&name),
+                Token::Text(text) => self.text(&text),
+            }
+        }
+    }
+
+    fn raw_start(&mut self, name: String, attributes: Vec<(String, String)>, self_closing: bool) {
+        let void = VOID.contains(&name.as_str()) || self_closing;
+        let element = known(ELEMENTS, &name).or_else(|| known(MATHML, &name));
+        let attributes = element.map(|element| sanitize(element, attributes)).unwrap_or_default();
+        // embeds only over https
+        let embed = element == Some("iframe")
+            && attributes
+                .iter()
+                .any(|(key, value)| *key == "src" && value.starts_with("https://"));
+        let element = match element {
+            Some("iframe") if !embed => None,
+            _ => element,
+        };
+        match element {
+            Some(element) if void => self.push(Node::Element(Element::new(element, attributes))),
+            Some(element) => self.open(element, attributes, true),
+            None if (DROPPED.contains(&name.as_str()) || name == "iframe") && !void => {
+                self.skip = Some((name, 0));
+            }
+            // unknown, the content stays
+            None => {}
+        }
+    }
+
+    /// closes the raw element of `name` and what is open inside it
+    fn raw_end(&mut self, name: &str) {
+        let found = self
+            .open
+            .iter()
+            .rposition(|open| !open.raw || open.element.name == name)
+            .filter(|at| self.open[*at].raw);
+        if let Some(at) = found {
+            while self.open.len() > at {
+                self.close();
+            }
+        }
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(text),
+            Event::Code(code) => {
+                if self.skip.is_none() {
+                    let mut element = Element::new("code", Vec::new());
+                    element.children.push(Node::Text(code.to_string()));
+                    self.push(Node::Element(element));
+                }
+            }
+            // left when math is not rendered
+            Event::InlineMath(latex) | Event::DisplayMath(latex) => {
+                let mut element = Element::new("code", vec![("class", "math".to_owned())]);
+                element.children.push(Node::Text(latex.to_string()));
+                self.push(Node::Element(element));
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.raw(html),
+            Event::FootnoteReference(label) => {
+                let n = self.footnote(label);
+                let mut link = Element::new("a", vec![("href", format!("#{}", label))]);
+                link.children.push(Node::Text(n.to_string()));
+                let mut sup = Element::new("sup", vec![("class", "footnote-reference".to_owned())]);
+                sup.children.push(Node::Element(link));
+                self.push(Node::Element(sup));
+            }
+            Event::SoftBreak => self.text("\n"),
+            Event::HardBreak => self.push(Node::Element(Element::new("br", Vec::new()))),
+            Event::Rule => self.push(Node::Element(Element::new("hr", Vec::new()))),
+            Event::TaskListMarker(checked) => {
+                let mut attributes = vec![("type", "checkbox".to_owned()), ("disabled", String::new())];
+                if *checked {
+                    attributes.push(("checked", String::new()));
+                }
+                self.push(Node::Element(Element::new("input", attributes)));
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Node> {
+        while !self.open.is_empty() {
+            self.close();
+        }
+        self.root
+    }
+}
+
+/// the tree of parsed markdown events
+pub fn build(events: &[Event]) -> Vec<Node> {
+    let mut builder = Builder::default();
+    for event in events {
+        builder.event(event);
+    }
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::Document;
+
+    fn html(md: &str) -> String {
+        Document::parse(md).to_tree().iter().map(Node::to_html).collect()
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            html("# 雪風\n\n- a *b*\n- [x] c\n"),
+            concat!(
+                r#"<h1 id="雪風">雪風</h1><ul><li>a <em>b</em></li>"#,
+                r#"<li><input type="checkbox" disabled="" checked="">c</li></ul>"#
+            )
+        );
+        assert_eq!(
+            html("| a | b |\n|:--|--:|\n| 1 | 2 |\n"),
+            concat!(
+                r#"<table><thead><tr><th style="text-align: left">a</th><th style="text-align: right">b</th></tr></thead>"#,
+                r#"<tbody><tr><td style="text-align: left">1</td><td style="text-align: right">2</td></tr></tbody></table>"#
+            )
+        );
+        assert_eq!(
+            html("```rust\nlet a = 1 < 2;\n```\n"),
+            r#"<pre><code class="language-rust">let a = 1 &lt; 2;
+</code></pre>"#
+        );
+        assert_eq!(
+            html("a[^n]\n\n[^n]: b\n"),
+            concat!(
+                r##"<p>a<sup class="footnote-reference"><a href="#n">1</a></sup></p>"##,
+                r#"<div class="footnote-definition" id="n"><sup class="footnote-definition-label">1</sup><p>b</p></div>"#
+            )
+        );
+        assert_eq!(
+            html("[a](javascript:alert(1)) ![b](/b.png)\n"),
+            r#"<p><a>a</a> <img src="/b.png" alt="b" loading="lazy"></p>"#
+        );
+    }
+
+    #[test]
+    fn raw_html() {
+        assert_eq!(
+            html("<script>alert(1)</script>\n\na <b onclick=\"x()\" class=c>b</b> <font>c</font>\n"),
+            r#"<p>a <b class="c">b</b> c</p>"#
+        );
+        assert_eq!(
+            html("<a href=\"jav&#x61;script:x\" title='&quot;t&quot;'>a</a>\n"),
+            r#"<p><a title="&quot;t&quot;">a</a></p>"#
+        );
+        // opened in one html block, closed in another
+        assert_eq!(
+            html("<details><summary>s</summary>\n\nbody\n\n</details>\n"),
+            "<details><summary>s</summary>\n<p>body</p></details>"
+        );
+        // a tag broken over lines, an iframe over http
+        assert_eq!(
+            html("<div\n  class=\"x\">\n<iframe src=\"http://a\">b</iframe>\n</div>\n"),
+            "<div class=\"x\">\n\n</div>"
+        );
+        // style only as the iframe height
+        assert_eq!(html("<div style=\"position: fixed\">a</div>\n"), "<div>a</div>");
+        assert_eq!(
+            html("<iframe src=\"https://a\" style=\"height: 15em;\"></iframe>\n"),
+            "<iframe src=\"https://a\" style=\"height: 15em;\"></iframe>"
+        );
+        assert_eq!(
+            html("<iframe src=\"https://a\" style=\"height: 1em; position: fixed;\"></iframe>\n"),
+            "<iframe src=\"https://a\"></iframe>"
+        );
+        // left open, closed with its paragraph
+        assert_eq!(html("a <span>b\n\nc\n"), "<p>a <span>b</span></p><p>c</p>");
+        assert_eq!(html("a </span> b &lt; c\n"), "<p>a  b &lt; c</p>");
+    }
+
+    #[test]
+    fn generated() {
+        let code = concat!(
+            "<pre class=\"hl\"><code class=\"language-rust\"><span class=\"hl-keyword\">let</span> a ",
+            "&lt; b;\n\nc\n</code></pre>\n"
+        );
+        let tree = Document::parse(code).to_tree();
+        let Node::Element(pre) = &tree[0] else {
+            panic!("no pre");
+        };
+        assert_eq!(pre.attribute("class"), Some("hl"));
+        assert_eq!(pre.text(), "let a < b;\n\nc\n");
+        let math = html("$x^2$\n");
+        assert!(math.starts_with(r#"<p><math xmlns="http://www.w3.org/1998/Math/MathML" display="inline">"#));
+        assert!(math.contains("<msup><mi>x</mi><mn>2</mn></msup>"));
+        assert_eq!(
+            html("[東京]{とう|きょう}\n"),
+            "<p><ruby>東<rp>(</rp><rt>とう</rt><rp>)</rp>京<rp>(</rp><rt>きょう</rt><rp>)</rp></ruby></p>"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! webmentions in the jf2 shape of `/api/mentions.jf2`
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Property {
+    LikeOf,
+    RepostOf,
+    InReplyTo,
+    BookmarkOf,
+    RsvpOf,
+    #[default]
+    #[serde(other)]
+    MentionOf,
+}
+
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Author {
+    pub name: String,
+    pub photo: String,
+    pub url: String,
+}
+
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Content {
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Mention {
+    pub url: String,
+    pub author: Author,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    pub wm_received: String,
+    pub wm_property: Property,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+}
+
+impl Mention {
+    /// rfc 3339, when it was published or else received
+    pub fn date(&self) -> &str {
+        self.published.as_deref().unwrap_or(&self.wm_received)
+    }
+
+    /// offsets differ between sites, compare these and not the strings
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.date()).ok()
+    }
+}
+
+/// sort key, oldest first and dates we can not read last
+fn by_date(mention: &Mention) -> (bool, Option<DateTime<FixedOffset>>) {
+    let timestamp = mention.timestamp();
+    (timestamp.is_none(), timestamp)
+}
+
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Feed {
+    pub children: Vec<Mention>,
+}
+
+/// a reply and the replies to it
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Thread {
+    pub mention: Mention,
+    pub replies: Vec<Thread>,
+}
+
+/// mentions split by what they are
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Grouped {
+    pub likes: Vec<Mention>,
+    pub reposts: Vec<Mention>,
+    /// oldest first
+    pub replies: Vec<Thread>,
+    pub mentions: Vec<Mention>,
+}
+
+/// a reply and everything below it, each reply is placed once
+fn thread(reply: &Mention, replies: &[Mention], seen: &mut Vec<String>) -> Thread {
+    seen.push(reply.url.clone());
+    let mut children = Vec::new();
+    for child in replies
+        .iter()
+        .filter(|r| r.in_reply_to.as_deref() == Some(reply.url.as_str()))
+    {
+        // a loop of replies comes back to one already placed
+        if !seen.contains(&child.url) {
+            children.push(thread(child, replies, seen));
+        }
+    }
+    Thread {
+        mention: reply.clone(),
+        replies: children,
+    }
+}
+
+impl Feed {
+    pub fn group(&self) -> Grouped {
+        let mut grouped = Grouped::default();
+        let mut replies = Vec::new();
+        for mention in self.children.iter().cloned() {
+            match mention.wm_property {
+                Property::LikeOf => grouped.likes.push(mention),
+                Property::RepostOf => grouped.reposts.push(mention),
+                Property::InReplyTo => replies.push(mention),
+                _ => grouped.mentions.push(mention),
+            }
+        }
+        replies.sort_by_key(by_date);
+        grouped.mentions.sort_by_key(by_date);
+        // a reply to another reply hangs under it, the rest are top level
+        let mut seen = Vec::new();
+        for reply in &replies {
+            let to_reply = replies
+                .iter()
+                .any(|r| Some(r.url.as_str()) == reply.in_reply_to.as_deref());
+            if !to_reply {
+                grouped.replies.push(thread(reply, &replies, &mut seen));
+            }
+        }
+        // replies in a loop reach no top level one, the oldest of a loop starts its thread
+        for reply in &replies {
+            if !seen.contains(&reply.url) {
+                grouped.replies.push(thread(reply, &replies, &mut seen));
+            }
+        }
+        grouped.replies.sort_by_key(|thread| by_date(&thread.mention));
+        grouped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let json = r#"{"type": "feed", "name": "Webmentions", "children": [
+            {"type": "entry", "author": {"type": "card", "name": "Tantek", "photo": "https://a/p.jpg", "url": "https://tantek.com/"},
+             "url": "https://x/1", "published": "2021-01-02T00:00:00+00:00", "wm-received": "2021-01-03T00:00:00Z",
+             "content": {"html": "<p>hi</p>", "text": "hi"}, "in-reply-to": "https://blog.gyara.moe/post/a/", "wm-property": "in-reply-to"},
+            {"type": "entry", "url": "https://x/2", "wm-received": "2021-01-01T00:00:00Z", "wm-property": "like-of"},
+            {"type": "entry", "url": "https://x/3", "wm-received": "2021-01-01T00:00:00Z", "wm-property": "something-new"}
+        ]}"#;
+        let feed: Feed = serde_json::from_str(json).unwrap();
+        assert_eq!(feed.children[0].author.name, "Tantek");
+        assert_eq!(feed.children[0].content.as_ref().unwrap().text, "hi");
+        assert_eq!(feed.children[1].wm_property, Property::LikeOf);
+        assert_eq!(feed.children[2].wm_property, Property::MentionOf);
+        assert_eq!(feed.children[1].date(), "2021-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn threads() {
+        let reply = |url: &str, to: &str, date: &str| Mention {
+            url: url.to_owned(),
+            in_reply_to: Some(to.to_owned()),
+            wm_received: date.to_owned(),
+            wm_property: Property::InReplyTo,
+            ..Default::default()
+        };
+        let feed = Feed {
+            children: vec![
+                reply("b", "post", "2021-01-01T02:00:00Z"),
+                reply("c", "a", "2021-01-01T03:00:00Z"),
+                // 01:00 utc, after b as a string
+                reply("a", "post", "2021-01-01T10:00:00+09:00"),
+                reply("x", "y", "2021-01-01T04:00:00Z"),
+                reply("y", "x", "2021-01-01T05:00:00Z"),
+                reply("z", "post", "yesterday"),
+            ],
+        };
+        let replies = feed.group().replies;
+        let urls: Vec<&str> = replies.iter().map(|t| t.mention.url.as_str()).collect();
+        assert_eq!(urls, ["a", "b", "x", "z"]);
+        assert_eq!(replies[0].replies[0].mention.url, "c");
+        assert_eq!(replies[2].replies[0].mention.url, "y");
+        assert!(replies[2].replies[0].replies.is_empty());
+    }
+}
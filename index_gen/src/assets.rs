@@ -0,0 +1,235 @@
+//! local assets kept next to posts, `post/<slug>/cover.webp`
+//! copied into `dist/assets` under a content hashed name, so they can be cached forever.
+use crate::images::{self, Variant};
+use blake2::Digest;
+use fubuki_types::markdown::escape;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// where a local asset ends up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Asset {
+    pub url: String,
+    pub size: Option<(usize, usize)>,
+    /// resized copies, raster images only
+    pub variants: Vec<Variant>,
+}
+
+impl Asset {
+    /// ` width="" height=""` for `<img>`
+    pub fn size_attributes(&self) -> String {
+        match self.size {
+            Some((width, height)) => format!(r#" width="{}" height="{}""#, width, height),
+            None => String::new(),
+        }
+    }
+
+    fn is_webp(&self) -> bool {
+        self.url.ends_with(".webp")
+    }
+
+    /// `srcset` of one format, the full size original is the largest candidate of its own
+    pub fn srcset(&self, mime: &str) -> String {
+        let mut set: Vec<String> = self
+            .variants
+            .iter()
+            .filter(|v| v.mime == mime)
+            .map(|v| format!("/assets/{} {}w", v.file, v.width))
+            .collect();
+        let original = self.is_webp() == (mime == "image/webp");
+        if let (Some((width, _)), true) = (self.size, original) {
+            set.push(format!("{} {}w", self.url, width));
+        }
+        set.join(", ")
+    }
+
+    /// `src` of the `<img>`, a webp original falls back to its widest copy
+    pub fn src(&self) -> String {
+        let widest = self
+            .variants
+            .iter()
+            .filter(|v| v.mime != "image/webp")
+            .max_by_key(|v| v.width);
+        match widest {
+            Some(variant) if self.is_webp() => format!("/assets/{}", variant.file),
+            _ => self.url.clone(),
+        }
+    }
+
+    pub fn fallback_mime(&self) -> Option<&str> {
+        self.variants
+            .iter()
+            .map(|v| v.mime.as_str())
+            .find(|m| *m != "image/webp")
+    }
+
+    /// `<img>`, inside a `<picture>` when there are variants
+    pub fn img(&self, alt: &str, title: &str) -> String {
+        let title = if title.is_empty() {
+            String::new()
+        } else {
+            format!(r#" title="{}""#, escape(title))
+        };
+        let fallback = match self.fallback_mime() {
+            Some(mime) => mime,
+            None => {
+                return format!(
+                    r#"<img src="{}" alt="{}"{}{} loading="lazy">"#,
+                    self.url,
+                    escape(alt),
+                    title,
+                    self.size_attributes()
+                )
+            }
+        };
+        let webp = self.srcset("image/webp");
+        let source = if webp.is_empty() {
+            String::new()
+        } else {
+            format!(
+                r#"<source type="image/webp" srcset="{}" sizes="{}">"#,
+                webp,
+                images::SIZES
+            )
+        };
+        format!(
+            r#"<picture>{}<img src="{}" srcset="{}" sizes="{}" alt="{}"{}{} loading="lazy"></picture>"#,
+            source,
+            self.src(),
+            self.srcset(fallback),
+            images::SIZES,
+            escape(alt),
+            title,
+            self.size_attributes()
+        )
+    }
+}
+
+pub struct Assets {
+    /// the post directory
+    source: PathBuf,
+    /// `dist/assets`
+    target: PathBuf,
+    /// image variants by source hash, kept between builds
+    cache: PathBuf,
+    published: RefCell<HashMap<PathBuf, Asset>>,
+}
+
+/// only paths relative to the post are ours
+pub fn is_local(url: &str) -> bool {
+    !(url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains(':'))
+}
+
+/// first 8 bytes of blake2b, in hex
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hasher = blake2::Blake2b512::new();
+    hasher.update(bytes);
+    hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Assets {
+    pub fn new(source: impl Into<PathBuf>, target: impl Into<PathBuf>, cache: impl Into<PathBuf>) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            cache: cache.into(),
+            published: RefCell::default(),
+        }
+    }
+
+    /// copy `post/<slug>/<path>` into dist
+    pub fn publish(&self, slug: &str, path: &str) -> anyhow::Result<Asset> {
+        let path = path.trim_start_matches("./");
+        let file = self.source.join(slug).join(path);
+        if let Some(asset) = self.published.borrow().get(&file) {
+            return Ok(asset.clone());
+        }
+        let bytes = fs::read(&file).map_err(|e| anyhow::Error::msg(format!("asset {} of {}: {}", path, slug, e)))?;
+        let hash = hash(&bytes);
+        let name = hashed_name(&file, &hash);
+        fs::create_dir_all(&self.target)?;
+        fs::write(self.target.join(&name), &bytes)?;
+        let size = imagesize::blob_size(&bytes).ok().map(|s| (s.width, s.height));
+        let ext = file.extension().unwrap_or_default().to_string_lossy();
+        let variants = if images::is_raster(&ext) {
+            let stem = name.trim_end_matches(&*ext).trim_end_matches('.');
+            let variants = images::variants(&bytes, stem, &hash, &self.cache)?;
+            for variant in variants.iter() {
+                fs::copy(
+                    images::cache_dir(&self.cache, &hash).join(&variant.file),
+                    self.target.join(&variant.file),
+                )?;
+            }
+            variants
+        } else {
+            Vec::new()
+        };
+        let asset = Asset {
+            url: format!("/assets/{}", name),
+            size,
+            variants,
+        };
+        self.published.borrow_mut().insert(file, asset.clone());
+        Ok(asset)
+    }
+}
+
+/// `cover.webp` -> `cover.0123456789abcdef.webp`
+fn hashed_name(file: &Path, hash: &str) -> String {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    match file.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, hash, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn local() {
+        assert!(is_local("cover.webp"));
+        assert!(is_local("./img/cover.webp"));
+        assert!(!is_local("/public/favicon.ico"));
+        assert!(!is_local("https://storage.googleapis.com/blog_gyara/img/a.png"));
+        assert!(!is_local("#top"));
+    }
+
+    #[test]
+    fn picture() {
+        let variant = |width: u32, mime: &str, ext: &str| Variant {
+            file: format!("a.00.{}.{}", width, ext),
+            width,
+            mime: mime.to_owned(),
+        };
+        let asset = Asset {
+            url: "/assets/a.00.png".to_owned(),
+            size: Some((1000, 500)),
+            variants: vec![variant(480, "image/webp", "webp"), variant(480, "image/jpeg", "jpg")],
+        };
+        let img = asset.img("雪", "");
+        assert!(img.starts_with(r#"<picture><source type="image/webp" srcset="/assets/a.00.480.webp 480w" sizes=""#));
+        assert!(img.contains(r#"srcset="/assets/a.00.480.jpg 480w, /assets/a.00.png 1000w""#));
+        assert!(img.ends_with(r#"alt="雪" width="1000" height="500" loading="lazy"></picture>"#));
+        // a webp original is not its own fallback
+        let asset = Asset {
+            url: "/assets/a.00.webp".to_owned(),
+            size: Some((400, 200)),
+            variants: vec![variant(400, "image/jpeg", "jpg")],
+        };
+        let img = asset.img("雪", "");
+        assert!(img.contains(r#"<source type="image/webp" srcset="/assets/a.00.webp 400w""#));
+        assert!(img.contains(r#"<img src="/assets/a.00.400.jpg" srcset="/assets/a.00.400.jpg 400w""#));
+    }
+
+    #[test]
+    fn name() {
+        assert_eq!(hashed_name(Path::new("post/a/cover.webp"), "0011"), "cover.0011.webp");
+        assert_eq!(hash(b"fubuki").len(), 16);
+    }
+}
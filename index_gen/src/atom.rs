@@ -1,9 +1,19 @@
-use crate::date;
-use atom_syndication::{CategoryBuilder, Content, Entry, Feed, FixedDateTime, Generator, Link, Person, Text};
+use crate::config;
+use atom_syndication::{
+    extension::{Extension, ExtensionMap},
+    CategoryBuilder, Content, Entry, Feed, FixedDateTime, Generator, Link, Person, Text,
+};
+use fubuki_types::{date, head, markdown};
+use std::collections::BTreeMap;
+
+pub use fubuki_types::head::SITE;
 
 #[derive(Clone)]
 pub struct Post {
     pub front_matter: fubuki_types::Post,
+    /// front matter block as written, kept to write the baked post back
+    pub raw_front_matter: String,
+    /// baked markdown
     pub content: String,
 }
 
@@ -13,59 +23,144 @@ fn right() -> Text {
 }
 
 impl Post {
+    /// an entry id never changes, it keeps the trailing slash the post urls used to have
     fn id(&self) -> String {
-        format!("https://blog.gyara.moe/post/{}/", self.front_matter.url)
+        format!("{}/", head::post_url(&self.front_matter.url))
+    }
+
+    fn url(&self) -> String {
+        head::post_url(&self.front_matter.url)
     }
 
     pub fn updated(&self) -> String {
         date::from_jp_to_iso(self.front_matter.date.clone()).unwrap()
     }
-}
 
-impl From<Post> for Entry {
-    fn from(post: Post) -> Entry {
+    pub fn into_entry(self, config: &config::Feed, author: &config::Author) -> Entry {
+        let lang = self.front_matter.lang.clone().unwrap_or_else(|| config.lang.clone());
+        let with_lang = |mut text: Text| {
+            text.lang = Some(lang.clone());
+            text
+        };
         let mut entry = Entry::default();
-        entry.set_title(post.front_matter.title.clone());
-        entry.set_id(post.id());
+        entry.set_title(with_lang(Text::plain(self.front_matter.title.clone())));
+        entry.set_id(self.id());
         // updated
-        let updated = post.updated();
+        let updated = self.updated();
         entry.set_updated(FixedDateTime::parse_from_rfc3339(&updated).unwrap());
         entry.set_published(FixedDateTime::parse_from_rfc3339(&updated).unwrap());
-        entry.set_authors(vec![get_me()]);
+        entry.set_authors(vec![person(author)]);
         // link
         let mut link = Link::default();
-        link.set_href(post.id());
+        link.set_href(self.url());
         link.set_rel("alternate");
         entry.set_links(vec![link]);
         entry.set_rights(right());
-        entry.set_summary(Text::plain(post.front_matter.summary));
-        // category
-        let mut category = CategoryBuilder::default();
-        category.label(Some(post.front_matter.category.clone()));
-        category.term(post.front_matter.category);
-        entry.set_categories(vec![category.build()]);
+        entry.set_summary(with_lang(Text::plain(self.front_matter.summary.clone())));
+        // category and tags
+        let mut categories = vec![category(&self.front_matter.category, "categories")];
+        categories.extend(self.front_matter.tags.iter().map(|tag| category(tag, "tags")));
+        entry.set_categories(categories);
         // content
-        let mut content = Content::default();
-        let mut html = String::new();
-        let parser = pulldown_cmark::Parser::new(&post.content);
-        pulldown_cmark::html::push_html(&mut html, parser);
-        let escaped = htmlescape::encode_minimal(&html);
-        content.set_value(escaped);
-        content.set_content_type("html".to_string());
-        entry.set_content(content);
+        if config.content == config::FeedContent::Full {
+            let mut content = Content::default();
+            let html = markdown::render_to(&self.content, markdown::Target::Feed).html;
+            content.set_value(resolve_urls(&html, &self.url()));
+            content.set_content_type("html".to_string());
+            content.set_base(self.url());
+            content.set_lang(lang.clone());
+            entry.set_content(content);
+        }
         entry
     }
 }
 
-fn get_me() -> Person {
+fn category(term: &str, scheme: &str) -> atom_syndication::Category {
+    let mut category = CategoryBuilder::default();
+    category.term(term);
+    category.label(Some(term.to_owned()));
+    category.scheme(Some(format!("{}/{}", SITE, scheme)));
+    category.build()
+}
+
+/// resolve `url` against the page at `base`, as a browser would
+fn resolve(url: &str, base: &str) -> String {
+    let scheme = url.find(':').is_some_and(|i| !url[..i].contains(['/', '?', '#']));
+    if scheme || url.is_empty() {
+        url.to_owned()
+    } else if url.starts_with("//") {
+        format!("https:{}", url)
+    } else if url.starts_with('/') {
+        format!("{}{}", SITE, url)
+    } else if url.starts_with('#') || url.starts_with('?') {
+        format!("{}{}", base, url)
+    } else {
+        // relative to the directory of the base, like a browser would
+        let mut path: Vec<&str> = base.split('/').collect();
+        path.pop();
+        let mut rest = url;
+        loop {
+            if let Some(r) = rest.strip_prefix("./") {
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("../") {
+                // never climb above the host
+                if path.len() > 3 {
+                    path.pop();
+                }
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        format!("{}/{}", path.join("/"), rest)
+    }
+}
+
+/// feed readers rarely honour `xml:base`, rewrite relative links in the html
+fn resolve_urls(html: &str, base: &str) -> String {
+    let mut resolved = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((i, attr)) = ["href=\"", "src=\"", "srcset=\""]
+        .iter()
+        .filter_map(|attr| rest.find(attr).map(|i| (i, *attr)))
+        .min()
+    {
+        let start = i + attr.len();
+        let Some(len) = rest[start..].find('"') else {
+            break;
+        };
+        resolved.push_str(&rest[..start]);
+        let value = &rest[start..start + len];
+        if attr == "srcset=\"" {
+            let candidates: Vec<String> = value
+                .split(',')
+                .map(|candidate| {
+                    let candidate = candidate.trim();
+                    match candidate.split_once(' ') {
+                        Some((url, width)) => format!("{} {}", resolve(url, base), width),
+                        None => resolve(candidate, base),
+                    }
+                })
+                .collect();
+            resolved.push_str(&candidates.join(", "));
+        } else {
+            resolved.push_str(&resolve(value, base));
+        }
+        rest = &rest[start + len..];
+    }
+    resolved.push_str(rest);
+    resolved
+}
+
+fn person(author: &config::Author) -> Person {
     let mut me = Person::default();
-    me.set_name("gyara");
-    me.set_email("me@gyara.moe".to_string());
-    me.set_uri("https://blog.gyara.moe/".to_string());
+    me.set_name(author.name.clone());
+    me.set_email(Some(author.email.clone()).filter(|e| !e.is_empty()));
+    me.set_uri(author.url.clone());
     me
 }
 
-fn gen_atom_feed() -> Feed {
+fn gen_atom_feed(hub: &str, author: &config::Author) -> Feed {
     let mut feed = Feed::default();
     // generator
     let mut generator = Generator::default();
@@ -74,16 +169,16 @@ fn gen_atom_feed() -> Feed {
     generator.set_version(env!("CARGO_PKG_VERSION").to_string());
     // website link
     let mut link = Link::default();
-    link.set_href("https://blog.gyara.moe/atom.xml");
+    link.set_href(format!("{}/atom.xml", SITE));
     link.set_rel("self");
     // pubsubhubbub
     let mut pubsubhubbub = Link::default();
-    pubsubhubbub.set_href("https://pubsubhubbub.appspot.com/");
+    pubsubhubbub.set_href(hub);
     pubsubhubbub.set_rel("hub");
     // feed
-    feed.set_authors(vec![get_me()]);
+    feed.set_authors(vec![person(author)]);
     feed.set_title("島風造船所");
-    feed.set_id("https://blog.gyara.moe/");
+    feed.set_id(format!("{}/", SITE));
     feed.set_generator(generator);
     feed.set_links(vec![link, pubsubhubbub]);
     feed.set_rights(right());
@@ -91,12 +186,186 @@ fn gen_atom_feed() -> Feed {
     feed
 }
 
-pub fn gather_posts(posts: Vec<Post>) -> Feed {
-    let mut feed = gen_atom_feed();
-    let entries: Vec<Entry> = posts.into_iter().map(std::convert::Into::into).collect();
+pub fn archive_name(n: usize) -> String {
+    format!("atom-archive-{}.xml", n)
+}
+
+fn link(href: String, rel: &str) -> Link {
+    let mut link = Link::default();
+    link.set_href(href);
+    link.set_rel(rel);
+    link
+}
+
+/// `<fh:archive/>` marks an archive document
+fn archive_marker() -> ExtensionMap {
+    let mut archive = Extension::default();
+    archive.set_name("fh:archive");
+    let mut fh = BTreeMap::new();
+    fh.insert("archive".to_owned(), vec![archive]);
+    let mut extensions = ExtensionMap::new();
+    extensions.insert("fh".to_owned(), fh);
+    extensions
+}
+
+fn set_entries(feed: &mut Feed, entries: Vec<Entry>) {
     if let Some(entry) = entries.first() {
         feed.set_updated(*entry.updated());
     }
     feed.set_entries(entries);
-    feed
+}
+
+/// the subscription feed and its RFC 5005 archives
+pub struct Feeds {
+    pub current: Feed,
+    /// oldest first, `archives[0]` is `atom-archive-1.xml`
+    pub archives: Vec<Feed>,
+}
+
+/// posts are sorted newest first
+/// posts past `max_entries` are archived, every page but the newest is full and never
+/// changes once written, the newest is written again until it is full.
+pub fn gather_posts(posts: Vec<Post>, config: &config::Feed, author: &config::Author) -> Feeds {
+    let mut entries: Vec<Entry> = posts.into_iter().map(|post| post.into_entry(config, author)).collect();
+    let size = config.archive_size.max(1);
+    let kept = config.max_entries.unwrap_or(usize::MAX).min(entries.len());
+    let pages = (entries.len() - kept).div_ceil(size);
+    let mut archives = Vec::with_capacity(pages);
+    for n in 1..=pages {
+        let mut feed = gen_atom_feed(&config.hub, author);
+        let mut links = vec![
+            link(format!("{}/{}", SITE, archive_name(n)), "self"),
+            link(format!("{}/atom.xml", SITE), "current"),
+        ];
+        if n > 1 {
+            links.push(link(format!("{}/{}", SITE, archive_name(n - 1)), "prev-archive"));
+        }
+        if n < pages {
+            links.push(link(format!("{}/{}", SITE, archive_name(n + 1)), "next-archive"));
+        }
+        feed.set_links(links);
+        feed.set_namespaces(BTreeMap::from([(
+            "fh".to_owned(),
+            "http://purl.org/syndication/history/1.0".to_owned(),
+        )]));
+        feed.set_extensions(archive_marker());
+        let end = entries.len() - (n - 1) * size;
+        set_entries(&mut feed, entries[end.saturating_sub(size).max(kept)..end].to_vec());
+        archives.push(feed);
+    }
+    entries.truncate(kept);
+    let mut current = gen_atom_feed(&config.hub, author);
+    if pages > 0 {
+        let mut links = current.links().to_vec();
+        links.push(link(format!("{}/{}", SITE, archive_name(pages)), "prev-archive"));
+        current.set_links(links);
+    }
+    set_entries(&mut current, entries);
+    Feeds { current, archives }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn post(n: usize) -> Post {
+        Post {
+            front_matter: fubuki_types::Post {
+                url: format!("2021-01-0{}-post", n),
+                date: "令和3/1/1".to_owned(),
+                category: "Tech".to_owned(),
+                tags: vec!["rust".to_owned(), "yew".to_owned()],
+                ..Default::default()
+            },
+            raw_front_matter: String::new(),
+            content: "[a](./b.png) [c](/d) [e](https://e.com) ![f](../g.png)\n".to_owned(),
+        }
+    }
+
+    #[test]
+    fn relative() {
+        let base = "https://blog.gyara.moe/post/a";
+        assert_eq!(resolve("b.png", base), "https://blog.gyara.moe/post/b.png");
+        assert_eq!(resolve("./b.png", base), "https://blog.gyara.moe/post/b.png");
+        assert_eq!(resolve("../b", base), "https://blog.gyara.moe/b");
+        assert_eq!(resolve("../../../b", base), "https://blog.gyara.moe/b");
+        assert_eq!(resolve("#top", base), "https://blog.gyara.moe/post/a#top");
+        assert_eq!(resolve("mailto:me@gyara.moe", base), "mailto:me@gyara.moe");
+        assert_eq!(
+            resolve_urls(r#"<img srcset="/a.480.jpg 480w, /a.jpg 960w">"#, base),
+            r#"<img srcset="https://blog.gyara.moe/a.480.jpg 480w, https://blog.gyara.moe/a.jpg 960w">"#
+        );
+    }
+
+    #[test]
+    fn entry() {
+        let config = config::Feed::default();
+        let entry = post(1).into_entry(&config, &config::Author::default());
+        let schemes: Vec<_> = entry
+            .categories()
+            .iter()
+            .map(|c| (c.term(), c.scheme().unwrap()))
+            .collect();
+        assert_eq!(schemes[1], ("rust", "https://blog.gyara.moe/tags"));
+        assert_eq!(schemes.len(), 3);
+        let content = entry.content().unwrap();
+        assert_eq!(content.lang(), Some("zh-Hans"));
+        let html = content.value().unwrap();
+        assert!(html.contains(r#"href="https://blog.gyara.moe/post/b.png""#));
+        assert!(html.contains(r#"href="https://blog.gyara.moe/d""#));
+        assert!(html.contains(r#"src="https://blog.gyara.moe/g.png""#));
+        assert_eq!(entry.id(), "https://blog.gyara.moe/post/2021-01-01-post/");
+        assert_eq!(entry.links()[0].href(), "https://blog.gyara.moe/post/2021-01-01-post");
+        assert_eq!(content.base(), Some("https://blog.gyara.moe/post/2021-01-01-post"));
+    }
+
+    #[test]
+    fn limit_and_summary() {
+        let config = config::Feed {
+            max_entries: Some(2),
+            content: config::FeedContent::Summary,
+            ..Default::default()
+        };
+        let feeds = gather_posts((1..5).map(post).collect(), &config, &config::Author::default());
+        assert_eq!(feeds.current.entries().len(), 2);
+        assert!(feeds.current.entries()[0].content().is_none());
+        // the rest is on a page not full yet
+        assert_eq!(feeds.archives.len(), 1);
+        let ids: Vec<&str> = feeds.archives[0].entries().iter().map(|e| e.id()).collect();
+        assert_eq!(ids, [post(3).id(), post(4).id()]);
+        let feeds = gather_posts(
+            (1..5).map(post).collect(),
+            &config::Feed::default(),
+            &config::Author::default(),
+        );
+        assert_eq!(feeds.current.entries().len(), 4);
+        assert!(feeds.archives.is_empty());
+    }
+
+    #[test]
+    fn archive() {
+        let config = config::Feed {
+            max_entries: Some(1),
+            archive_size: 2,
+            ..Default::default()
+        };
+        // newest first, 5 -> 1
+        let feeds = gather_posts((1..6).rev().map(post).collect(), &config, &config::Author::default());
+        assert_eq!(feeds.archives.len(), 2);
+        let ids = |feed: &Feed| -> Vec<String> { feed.entries().iter().map(|e| e.id().to_owned()).collect() };
+        assert_eq!(ids(&feeds.archives[0]), [post(2).id(), post(1).id()]);
+        assert_eq!(ids(&feeds.current), [post(5).id()]);
+        let rels = |feed: &Feed| -> Vec<(String, String)> {
+            feed.links()
+                .iter()
+                .map(|l| (l.rel().to_owned(), l.href().rsplit('/').next().unwrap().to_owned()))
+                .collect()
+        };
+        assert!(rels(&feeds.archives[0]).contains(&("next-archive".to_owned(), archive_name(2))));
+        assert!(rels(&feeds.archives[1]).contains(&("prev-archive".to_owned(), archive_name(1))));
+        assert!(rels(&feeds.current).contains(&("prev-archive".to_owned(), archive_name(2))));
+        let xml = feeds.archives[0].to_string();
+        assert!(xml.contains("<fh:archive"));
+        assert!(xml.contains(r#"xmlns:fh="http://purl.org/syndication/history/1.0""#));
+    }
 }
@@ -0,0 +1,216 @@
+//! bake build time output into the markdown we ship
+//! the client renders the baked file, the feed renders the same text,
+//! so both get the same html.
+use crate::{
+    assets::{self, Assets},
+    highlight, images,
+};
+use fubuki_types::markdown::{self, shortcode};
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+use std::ops::Range;
+
+/// bake a post
+/// - fenced code blocks become highlighted html blocks, inside lists or quotes
+///   every line of the block repeats the markers of its containers.
+/// - local images are published with a hashed name and get their size.
+/// - math becomes MathML, what latex2mathml can not convert is left for the build check.
+pub fn bake(md: &str, slug: &str, assets: &Assets) -> anyhow::Result<String> {
+    let mut replaces: Vec<(Range<usize>, String)> = Vec::new();
+    let mut block: Option<(usize, String, String)> = None;
+    let mut image: Option<(Range<usize>, String, String, String)> = None;
+    for (event, range) in Parser::new_ext(md, markdown::options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
+                block = Some((range.start, lang.to_string(), String::new()));
+            }
+            Event::Start(Tag::Image { dest_url, title, .. }) if assets::is_local(&dest_url) => {
+                image = Some((range, dest_url.to_string(), title.to_string(), String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, _, code)) = block.as_mut() {
+                    code.push_str(&text);
+                } else if let Some((_, _, _, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((start, lang, code)) = block.take() {
+                    let prefix = continuation(&md[..start]);
+                    let html = highlight::highlight(&lang, &code).replace('\n', &format!("\n{}", prefix));
+                    replaces.push((start..range.end, html));
+                }
+            }
+            // alt text is plain, math in it stays latex
+            Event::InlineMath(latex) | Event::DisplayMath(latex) if image.is_some() => {
+                if let Some((_, _, _, alt)) = image.as_mut() {
+                    alt.push_str(&latex);
+                }
+            }
+            Event::InlineMath(latex) => {
+                if let Ok(mathml) = markdown::to_mathml(&latex, false) {
+                    replaces.push((range, math_html(&mathml)));
+                }
+            }
+            Event::DisplayMath(latex) => {
+                if let Ok(mathml) = markdown::to_mathml(&latex, true) {
+                    replaces.push((range, math_html(&mathml)));
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((range, src, title, alt)) = image.take() {
+                    let asset = assets.publish(slug, &src)?;
+                    replaces.push((range, asset.img(&alt, &title)));
+                }
+            }
+            _ => {}
+        }
+    }
+    for mut call in shortcode::find(md) {
+        match call.args.get("src") {
+            Some(src) if call.name == "figure" && assets::is_local(src) => {
+                let asset = assets.publish(slug, src)?;
+                if let Some((width, height)) = asset.size {
+                    call.args.set("width", width.to_string());
+                    call.args.set("height", height.to_string());
+                }
+                if let Some(fallback) = asset.fallback_mime() {
+                    call.args.set("srcset", asset.srcset(fallback));
+                    call.args.set("webp", asset.srcset("image/webp"));
+                    call.args.set("sizes", images::SIZES.to_owned());
+                }
+                call.args.set("src", asset.src());
+                replaces.push((call.range.clone(), call.to_source()));
+            }
+            _ => {}
+        }
+    }
+    // outer first, a replace inside one already made is dropped
+    replaces.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+
+    let mut baked = String::with_capacity(md.len());
+    let mut last = 0;
+    for (range, replace) in replaces {
+        if range.start < last {
+            continue;
+        }
+        baked.push_str(&md[last..range.start]);
+        baked.push_str(&replace);
+        last = range.end;
+    }
+    baked.push_str(&md[last..]);
+    Ok(baked)
+}
+
+/// what the next lines of a block need in front to stay in its containers
+/// `> - ` on the line of the fence becomes `>   `.
+fn continuation(before: &str) -> String {
+    let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+    line.chars()
+        .map(|c| if c == '>' || c.is_whitespace() { c } else { ' ' })
+        .collect()
+}
+
+/// MathML as inline html, text between its tags is escaped so markdown leaves it alone
+/// emphasis, smart quotes, table pipes and `$` would all change it otherwise.
+fn math_html(mathml: &str) -> String {
+    let mut html = String::with_capacity(mathml.len());
+    let (mut in_tag, mut in_entity) = (false, false);
+    for (i, c) in mathml.char_indices() {
+        if in_tag {
+            in_tag = c != '>';
+            html.push(c);
+        } else if in_entity || c == '&' {
+            // latex2mathml writes some chars as entities, `&lang;`
+            in_entity = c != ';';
+            html.push(c);
+        } else if c == '<' && mathml[i + 1..].starts_with(|n: char| n.is_ascii_alphabetic() || n == '/') {
+            in_tag = true;
+            html.push(c);
+        } else if c.is_ascii_punctuation() {
+            html.push_str(&format!("&#{};", c as u32));
+        } else {
+            html.push(c);
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn assets() -> Assets {
+        Assets::new("no-such-post-dir", "no-such-dist", "no-such-cache")
+    }
+
+    #[test]
+    fn code_block() {
+        let md = "para\n\n```python\nprint(1)\n\nprint(2)\n```\n\nafter\n";
+        let baked = bake(md, "post", &assets()).unwrap();
+        assert!(baked.starts_with("para\n\n<pre class=\"hl\">"));
+        assert!(baked.ends_with("</code></pre>\n\nafter\n"));
+        let (html, _) = markdown::render(&baked);
+        assert!(html.contains("<p>after</p>"));
+        assert!(!html.contains("```"));
+    }
+
+    #[test]
+    fn nested_code_block() {
+        let md = "- item\n\n  ```rust\n  fn main() {}\n\n  main();\n  ```\n- next\n\n> 1. ```\n>    a\n>\n>    b\n>    ```\n";
+        let baked = bake(md, "post", &assets()).unwrap();
+        assert!(baked.starts_with("- item\n\n  <pre class=\"hl\">"));
+        assert!(baked.contains("> 1. <pre class=\"hl\">"));
+        assert!(!baked.contains("```"));
+        let (html, _) = markdown::render(&baked);
+        assert_eq!(html.matches("<ul>").count(), 1);
+        assert_eq!(html.matches("<li>").count(), 3);
+        assert!(html.contains("</pre>\n</li>\n<li>\n<p>next</p>"));
+        assert!(html.contains("a\n\nb\n</span></code></pre>\n</li>\n</ol>\n</blockquote>"));
+    }
+
+    #[test]
+    fn math() {
+        let md = "a $x*y*z$ b $a \\slashed{=} b \\langle$\n\n> $$\n> \\frac{a}{b} < c\n> $$\n\n$\\foo$\n";
+        let baked = bake(md, "post", &assets()).unwrap();
+        assert!(baked.starts_with(r#"a <math xmlns="http://www.w3.org/1998/Math/MathML" display="inline">"#));
+        assert!(baked.contains(r#"> <math xmlns="http://www.w3.org/1998/Math/MathML" display="block">"#));
+        assert!(baked.ends_with("$\\foo$\n"));
+        let rendered = markdown::render_to(&baked, markdown::Target::Web);
+        assert!(rendered.html.contains("<mo>*</mo>"));
+        assert!(!rendered.html.contains("<em>"));
+        assert!(rendered.html.contains("<mo>&lt;</mo>"));
+        assert!(rendered.html.contains("<mo>=\u{338}</mo>"));
+        assert!(rendered.html.contains("\u{27e8}"));
+        assert_eq!(rendered.errors.len(), 1);
+    }
+
+    #[test]
+    fn local_image() {
+        let root = std::env::temp_dir().join(format!("fubuki-bake-{}", std::process::id()));
+        let post = root.join("post").join("shimakaze");
+        fs::create_dir_all(&post).unwrap();
+        // smallest gif, 3x2
+        let gif = b"GIF89a\x03\x00\x02\x00\x00\x00\x00;";
+        fs::write(post.join("cover.gif"), gif).unwrap();
+        let assets = Assets::new(root.join("post"), root.join("dist"), root.join("cache"));
+        let md = "![島風](cover.gif)\n\n![](https://example.com/a.png)\n\n{{< figure src=cover.gif >}}\n\n![a $x$ b](cover.gif)\n\n```md\n{{< figure src=\"./missing.png\" >}}\n```\n";
+        let baked = bake(md, "shimakaze", &assets).unwrap();
+        let name = format!("cover.{}.gif", assets::hash(gif));
+        assert!(baked.starts_with(&format!(
+            r#"<img src="/assets/{}" alt="島風" width="3" height="2" loading="lazy">"#,
+            name
+        )));
+        assert!(baked.contains("![](https://example.com/a.png)"));
+        assert!(baked.contains(&format!(
+            r#"{{{{< figure src="/assets/{}" width="3" height="2" >}}}}"#,
+            name
+        )));
+        assert!(baked.contains(r#"alt="a x b""#));
+        // the shortcode in code is shown, not published
+        assert!(baked.contains("missing.png"));
+        assert!(root.join("dist").join(name).exists());
+        assert!(bake("![](missing.png)", "shimakaze", &assets).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
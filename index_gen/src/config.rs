@@ -0,0 +1,196 @@
+//! build settings, read from `fubuki.yml`
+//! every field has a default, a missing file means all defaults.
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// the whole post
+    #[default]
+    Full,
+    /// only the summary, readers follow the link
+    Summary,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Feed {
+    /// `xml:lang` of posts without a `lang` in their front matter
+    pub lang: String,
+    /// newest posts kept in `atom.xml`, all of them when unset
+    pub max_entries: Option<usize>,
+    pub content: FeedContent,
+    /// entries per RFC 5005 archive page
+    pub archive_size: usize,
+    /// WebSub hub, linked from the feed and pinged by `publish`
+    pub hub: String,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Self {
+            lang: "zh-Hans".to_owned(),
+            max_entries: None,
+            content: FeedContent::default(),
+            archive_size: 10,
+            hub: "https://pubsubhubbub.appspot.com/".to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Publish {
+    /// asked to send webmentions for the feed, none to skip
+    pub webmention_app: Option<String>,
+    /// send webmentions for the links of changed posts ourselves
+    pub webmentions: bool,
+}
+
+impl Default for Publish {
+    fn default() -> Self {
+        Self {
+            webmention_app: None,
+            webmentions: true,
+        }
+    }
+}
+
+/// received webmentions, baked at build time
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Mentions {
+    /// serves `/api/mentions.jf2`
+    pub base: String,
+    pub per_page: usize,
+}
+
+impl Default for Mentions {
+    fn default() -> Self {
+        Self {
+            base: "https://webmention.io".to_owned(),
+            per_page: 1000,
+        }
+    }
+}
+
+/// open graph images
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Og {
+    /// directory of the fonts, images are skipped without any
+    pub fonts: String,
+}
+
+impl Default for Og {
+    fn default() -> Self {
+        Self {
+            fonts: "fonts".to_owned(),
+        }
+    }
+}
+
+/// the post list of the client
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Posts {
+    pub page_size: usize,
+}
+
+impl Default for Posts {
+    fn default() -> Self {
+        Self { page_size: 5 }
+    }
+}
+
+/// site palettes, checked for contrast
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Theme {
+    pub file: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            file: "public/themes.yml".to_owned(),
+        }
+    }
+}
+
+/// installable app and offline reading
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Pwa {
+    /// square svg the app icons are drawn from
+    pub icon: String,
+}
+
+impl Default for Pwa {
+    fn default() -> Self {
+        Self {
+            icon: "public/icon/square.svg".to_owned(),
+        }
+    }
+}
+
+/// the person behind the site, for the feed and the `h-card`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Author {
+    pub name: String,
+    pub url: String,
+    pub email: String,
+    pub photo: String,
+    pub note: String,
+}
+
+impl Default for Author {
+    fn default() -> Self {
+        Self {
+            name: "gyara".to_owned(),
+            url: "https://blog.gyara.moe/".to_owned(),
+            email: "me@gyara.moe".to_owned(),
+            photo: "https://blog.gyara.moe/public/icon/square.svg".to_owned(),
+            note: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Config {
+    pub author: Author,
+    pub feed: Feed,
+    pub publish: Publish,
+    pub mentions: Mentions,
+    pub og: Og,
+    pub theme: Theme,
+    pub posts: Posts,
+    pub pwa: Pwa,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(yaml) => Ok(serde_yaml::from_str(&yaml)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn partial() {
+        let config: Config = serde_yaml::from_str("feed:\n  max_entries: 10\n  content: summary\n").unwrap();
+        assert_eq!(config.feed.max_entries, Some(10));
+        assert_eq!(config.feed.content, FeedContent::Summary);
+        assert_eq!(config.feed.lang, "zh-Hans");
+        assert_eq!(config.posts.page_size, 5);
+    }
+}
@@ -1,4 +1,4 @@
-pub use fubuki_types::{FrontMatter, Post};
+pub use fubuki_types::FrontMatter;
 
 /// find front matter from content
 fn find_front_matter(content: &str) -> Option<(String, String)> {
@@ -25,6 +25,12 @@ pub fn parse_front_matter(content: String) -> anyhow::Result<(FrontMatter, Strin
     Err(anyhow::Error::msg("Front matter parse failed"))
 }
 
+/// front matter block with an `image:` line added, the rest kept as written
+pub fn with_image(raw: &str, image: &str) -> String {
+    let body = raw.strip_suffix("---\n").unwrap_or(raw);
+    format!("{}image: \"{}\"\n---\n", body, image)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -45,6 +51,14 @@ mod test {
         assert!(front_matter_transfer(input).is_ok());
     }
     #[test]
+    fn image() {
+        let raw = "---\ntitle: a\ndate: 令和1/5/11\ncategory: c\ntags: []\nsummary: s\n---\n";
+        let raw = with_image(raw, "https://blog.gyara.moe/og/a.png");
+        assert!(raw.ends_with("summary: s\nimage: \"https://blog.gyara.moe/og/a.png\"\n---\n"));
+        let (fm, _) = parse_front_matter([raw.as_str(), "body"].concat()).unwrap();
+        assert_eq!(fm.image.as_deref(), Some("https://blog.gyara.moe/og/a.png"));
+    }
+    #[test]
     fn remove_time() {
         let input = r#"
             title : Goodbye Demon
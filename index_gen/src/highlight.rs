@@ -0,0 +1,64 @@
+//! build time syntax highlighting
+//! output is class based (`hl-keyword`, `hl-string` ..), colors come from the client stylesheet.
+use std::sync::OnceLock;
+use syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+const STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// render a fenced code block to `<pre class="hl">`
+/// unknown languages fall back to plain text.
+pub fn highlight(lang: &str, code: &str) -> String {
+    let syntaxes = syntaxes();
+    let lang = lang.split_whitespace().next().unwrap_or_default();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator.parse_html_for_line_which_includes_newline(line).is_err() {
+            // syntax definition broken, give up on colors but keep the code
+            return plain(lang, code);
+        }
+    }
+    format!(
+        "<pre class=\"hl\"><code class=\"language-{}\">{}</code></pre>",
+        htmlescape::encode_attribute(lang),
+        generator.finalize()
+    )
+}
+
+fn plain(lang: &str, code: &str) -> String {
+    format!(
+        "<pre class=\"hl\"><code class=\"language-{}\">{}</code></pre>",
+        htmlescape::encode_attribute(lang),
+        htmlescape::encode_minimal(code)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn python() {
+        let html = highlight("python", "import os\nprint('hi')\n");
+        assert!(html.starts_with(r#"<pre class="hl"><code class="language-python">"#));
+        assert!(html.contains("hl-keyword"));
+        assert!(html.contains("hl-string"));
+    }
+
+    #[test]
+    fn unknown_language() {
+        let html = highlight("no-such-lang", "a < b\n");
+        assert!(html.contains("a &lt; b"));
+    }
+}
@@ -0,0 +1,255 @@
+//! static html around the app shell
+//! trunk leaves `index.html` in the stage directory. It gets the site `h-card`, and every
+//! post a copy at `post/<slug>.html` carrying its `h-entry`, for readers without wasm
+//! and for microformats parsers, and `404.html` for paths that lead nowhere. The client
+//! drops `#prerender` once it starts.
+use crate::{atom::Post, config};
+use fubuki_types::{
+    date,
+    head::{self, Head, Tag},
+    markdown::{self, escape},
+};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// the site wide `h-card`, hidden, the client reads it back for `p-author`
+pub fn h_card(author: &config::Author) -> String {
+    let mut card = format!(
+        r#"<div id="site-card" class="h-card" hidden><a class="p-name u-url" rel="me" href="{}">{}</a>"#,
+        escape(&author.url),
+        escape(&author.name)
+    );
+    if !author.photo.is_empty() {
+        card.push_str(&format!(
+            r#"<img class="u-photo" src="{}" alt="{}">"#,
+            escape(&author.photo),
+            escape(&author.name)
+        ));
+    }
+    if !author.email.is_empty() {
+        card.push_str(&format!(
+            r#"<a class="u-email" href="mailto:{0}">{0}</a>"#,
+            escape(&author.email)
+        ));
+    }
+    if !author.note.is_empty() {
+        card.push_str(&format!(r#"<p class="p-note">{}</p>"#, escape(&author.note)));
+    }
+    card.push_str("</div>");
+    card
+}
+
+/// a leading `# title` wins over the front matter, as in the client
+pub fn title_and_body(post: &Post) -> (String, &str) {
+    match post.content.strip_prefix("# ") {
+        Some(rest) => {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            (rest[..end].trim().to_owned(), &rest[end..])
+        }
+        None => (post.front_matter.title.clone(), &post.content),
+    }
+}
+
+pub fn h_entry(post: &Post, author: &config::Author) -> String {
+    let fm = &post.front_matter;
+    let (title, body) = title_and_body(post);
+    let url = format!("/post/{}", fm.url);
+    let published = date::from_jp_to_iso(fm.date.clone()).unwrap_or_default();
+    let categories: String = std::iter::once(&fm.category)
+        .chain(fm.tags.iter())
+        .map(|c| format!(r#" <span class="p-category">{}</span>"#, escape(c)))
+        .collect();
+    format!(
+        concat!(
+            r#"<article id="prerender" class="h-entry">"#,
+            r#"<h1 class="p-name">{title}</h1>"#,
+            r#"<p><a class="u-url" href="{url}"><time class="dt-published" datetime="{published}">{date}</time></a>"#,
+            r#" <a class="p-author h-card" href="{author_url}">{author}</a>{categories}</p>"#,
+            r#"<p class="p-summary">{summary}</p>"#,
+            r#"<div class="e-content">{content}</div>"#,
+            "</article>"
+        ),
+        title = escape(&title),
+        url = url,
+        published = published,
+        date = escape(&fm.date),
+        author_url = escape(&author.url),
+        author = escape(&author.name),
+        categories = categories,
+        summary = escape(&fm.summary),
+        content = markdown::render_to(body, markdown::Target::Web).html,
+    )
+}
+
+/// the same `<head>` the client sets for the post
+pub fn head(post: &Post, author: &config::Author, image: Option<&String>) -> Head {
+    let fm = &post.front_matter;
+    let (title, _) = title_and_body(post);
+    let mut head = Head::page(title, format!("/post/{}", fm.url));
+    if !fm.summary.is_empty() {
+        head.description = fm.summary.clone();
+    }
+    head.image = image.cloned();
+    head.article = Some(head::Article {
+        published: date::from_jp_to_iso(fm.date.clone()).unwrap_or_default(),
+        section: fm.category.clone(),
+        tags: fm.tags.clone(),
+        author: author.name.clone(),
+        author_url: author.url.clone(),
+    });
+    head
+}
+
+/// drop the element of `tag` already in the page
+fn remove_tag(page: &str, tag: &Tag) -> String {
+    let (start, end) = match tag {
+        Tag::Name(name, _) => (format!(r#"<meta name="{}""#, name), ">"),
+        Tag::Property(property, _) => (format!(r#"<meta property="{}""#, property), ">"),
+        Tag::Link(rel, _) => (format!(r#"<link rel="{}""#, rel), ">"),
+        Tag::JsonLd(_) => (r#"<script type="application/ld+json">"#.to_owned(), "</script>"),
+    };
+    let Some(from) = page.find(&start) else {
+        return page.to_owned();
+    };
+    match page[from..].find(end) {
+        Some(to) => [&page[..from], &page[from + to + end.len()..]].concat(),
+        None => page.to_owned(),
+    }
+}
+
+/// replace the tags of `tags` in the page `<head>`
+fn set_head(page: &str, tags: &[Tag]) -> String {
+    let page = tags.iter().fold(page.to_owned(), |page, tag| remove_tag(&page, tag));
+    let html: String = tags.iter().map(Tag::to_html).collect();
+    match page.find("</head>") {
+        Some(end) => [&page[..end], &html, &page[end..]].concat(),
+        None => page,
+    }
+}
+
+fn insert_before_body_end(shell: &str, html: &str) -> String {
+    match shell.rfind("</body>") {
+        Some(end) => [&shell[..end], html, &shell[end..]].concat(),
+        None => [shell, html].concat(),
+    }
+}
+
+/// `404.html`, served by the host for unknown paths, the client shows its own page over it
+pub fn not_found(shell: &str) -> String {
+    let head = Head::page("404", "/404");
+    let page = shell.replacen(
+        "<title>島風造船所</title>",
+        &format!("<title>{}</title>", escape(&head.full_title())),
+        1,
+    );
+    let mut tags: Vec<Tag> = head
+        .tags()
+        .into_iter()
+        .filter(|tag| !matches!(tag, Tag::Link("canonical", _) | Tag::Property("og:url", _)))
+        .collect();
+    tags.push(Tag::Name("robots", "noindex".to_owned()));
+    let page = set_head(&page, &tags);
+    insert_before_body_end(
+        &page,
+        concat!(
+            r#"<article id="prerender"><h1>404</h1>"#,
+            "<p>お探しのページは見つかりませんでした。</p>",
+            r#"<p><a href="/">トップへ戻る</a></p></article>"#
+        ),
+    )
+}
+
+/// `index.html` with the `h-card` and client settings, `post/<slug>.html` for every post
+/// `images` are the open graph images by slug.
+pub fn write(
+    dist: &Path,
+    posts: &[Post],
+    config: &config::Config,
+    images: &BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    let author = &config.author;
+    let index = dist.join("index.html");
+    let Ok(shell) = fs::read_to_string(&index) else {
+        println!("::  no index.html in {}, skip static pages", dist.to_string_lossy());
+        return Ok(());
+    };
+    let card = h_card(author);
+    // a rebuild over the same stage keeps one card
+    let shell = if shell.contains(r#"id="site-card""#) {
+        shell
+    } else {
+        insert_before_body_end(&shell, &card)
+    };
+    // the shell answers every route, so no url of its own
+    let mut site: Vec<Tag> = Head::page("", "/")
+        .tags()
+        .into_iter()
+        .filter(|tag| !matches!(tag, Tag::Link("canonical", _) | Tag::Property("og:url", _)))
+        .collect();
+    site.push(Tag::Name("fubuki-page-size", config.posts.page_size.to_string()));
+    let shell = set_head(&shell, &site);
+    fs::write(&index, &shell)?;
+    fs::write(dist.join("404.html"), not_found(&shell))?;
+    let target = dist.join("post");
+    fs::create_dir_all(&target)?;
+    for post in posts {
+        let head = head(post, author, images.get(&post.front_matter.url));
+        let page = shell.replacen(
+            "<title>島風造船所</title>",
+            &format!("<title>{}</title>", escape(&head.full_title())),
+            1,
+        );
+        let page = set_head(&page, &head.tags());
+        let page = insert_before_body_end(&page, &h_entry(post, author));
+        fs::write(target.join(format!("{}.html", post.front_matter.url)), page)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entry() {
+        let post = Post {
+            front_matter: fubuki_types::Post {
+                url: "2021-04-01-a".to_owned(),
+                date: "令和3年4月1日".to_owned(),
+                title: "front".to_owned(),
+                summary: "s & t".to_owned(),
+                category: "Tech".to_owned(),
+                tags: vec!["rust".to_owned()],
+                ..Default::default()
+            },
+            raw_front_matter: String::new(),
+            content: "# 雪風\n\nbody\n".to_owned(),
+        };
+        let html = h_entry(&post, &config::Author::default());
+        assert!(html.contains(r#"<h1 class="p-name">雪風</h1>"#));
+        assert!(html.contains(r#"<time class="dt-published" datetime="2021-04-01T00:00:00Z">"#));
+        assert!(html.contains(r#"<a class="u-url" href="/post/2021-04-01-a">"#));
+        assert!(html.contains(r#"<span class="p-category">Tech</span> <span class="p-category">rust</span>"#));
+        assert!(html.contains(r#"<p class="p-summary">s &amp; t</p>"#));
+        assert!(html.contains(r#"<div class="e-content"><p>body</p>"#));
+        let page = set_head(
+            r#"<head><meta name="description" content="old" /></head>"#,
+            &head(&post, &config::Author::default(), None).tags(),
+        );
+        assert!(!page.contains("old"));
+        assert!(page.contains(r#"<meta name="description" content="s &amp; t" />"#));
+        assert!(page.contains(r#"<link rel="canonical" href="https://blog.gyara.moe/post/2021-04-01-a" />"#));
+        assert!(page.contains(r#"<meta property="og:title" content="雪風" />"#));
+        assert!(page.contains(r#""datePublished":"2021-04-01T00:00:00Z""#));
+        assert_eq!(
+            page,
+            set_head(&page, &head(&post, &config::Author::default(), None).tags())
+        );
+        let missing = not_found(r#"<head><title>島風造船所</title></head><body></body>"#);
+        assert!(missing.contains("<title>島風造船所 - 404</title>"));
+        assert!(missing.contains(r#"<meta name="robots" content="noindex" />"#));
+        assert!(!missing.contains("canonical"));
+        assert!(missing.contains(r#"<article id="prerender"><h1>404</h1>"#));
+        let card = h_card(&config::Author::default());
+        assert!(card.contains(r#"class="p-name u-url" rel="me" href="https://blog.gyara.moe/">gyara</a>"#));
+    }
+}
@@ -0,0 +1,205 @@
+//! responsive variants of local raster images
+//! every width gets a WebP and a JPEG (PNG when there is alpha) fallback, a WebP
+//! original also gets a fallback at its own size. The pure-Rust WebP encoder is lossless
+//! only, so photos are posterized a little first, near-lossless as libwebp calls it.
+//! Variants are cached by the source hash and the settings below, so a rebuild only
+//! encodes new images.
+use crate::assets;
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// widths we generate, wider than the source are skipped
+pub const WIDTHS: &[u32] = &[480, 960, 1440];
+/// the article column is 572px, with 15px margins on small screens
+pub const SIZES: &str = "(min-width: 602px) 572px, calc(100vw - 30px)";
+/// no variant may be bigger than this, the build stops otherwise
+pub const BUDGET: usize = 256 * 1024;
+/// jpeg quality steps tried to stay inside the budget
+const QUALITIES: &[u8] = &[85, 75, 60, 45];
+/// low bits dropped per channel before the lossless webp encoder, tried in order
+const POSTERIZE: &[u8] = &[0, 2, 3];
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Variant {
+    pub file: String,
+    pub width: u32,
+    pub mime: String,
+}
+
+pub fn is_raster(ext: &str) -> bool {
+    matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg" | "webp")
+}
+
+/// everything that shapes the variants, part of the cache key
+fn settings() -> String {
+    format!("{:?} {:?} {:?} {}", WIDTHS, QUALITIES, POSTERIZE, BUDGET)
+}
+
+/// clear the low `bits` of each color channel, long runs compress far better
+fn posterize(image: &DynamicImage, bits: u8) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    if bits > 0 {
+        let (mask, half) = (0xff << bits, 1 << (bits - 1));
+        for pixel in rgba.pixels_mut() {
+            for channel in pixel.0.iter_mut().take(3) {
+                *channel = (*channel & mask) | half;
+            }
+        }
+    }
+    let posterized = DynamicImage::ImageRgba8(rgba);
+    if image.color().has_alpha() {
+        posterized
+    } else {
+        DynamicImage::ImageRgb8(posterized.to_rgb8())
+    }
+}
+
+fn encode(image: &DynamicImage, mime: &str, quality: u8) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    match mime {
+        "image/webp" => posterize(image, quality).write_with_encoder(WebPEncoder::new_lossless(&mut buf))?,
+        "image/png" => image.write_with_encoder(PngEncoder::new(&mut buf))?,
+        _ => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, quality))?,
+    }
+    Ok(buf)
+}
+
+/// fallback encoded inside the budget if we can
+fn encode_fallback(image: &DynamicImage, mime: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for quality in QUALITIES {
+        bytes = encode(image, mime, *quality)?;
+        if bytes.len() <= BUDGET || mime != "image/jpeg" {
+            break;
+        }
+    }
+    Ok(bytes)
+}
+
+/// the first webp that beats the fallback, if any does
+fn encode_webp(image: &DynamicImage, fallback: usize) -> anyhow::Result<Option<Vec<u8>>> {
+    for bits in POSTERIZE {
+        let webp = encode(image, "image/webp", *bits)?;
+        if webp.len() <= BUDGET && webp.len() < fallback {
+            return Ok(Some(webp));
+        }
+    }
+    Ok(None)
+}
+
+/// where the variants of a source are cached
+pub fn cache_dir(cache: &Path, hash: &str) -> PathBuf {
+    cache.join(format!("{}-{}", hash, assets::hash(settings().as_bytes())))
+}
+
+/// generate variants of `bytes` into [`cache_dir`]
+/// `name` is the hashed file stem, `cover.0123456789abcdef`.
+pub fn variants(bytes: &[u8], name: &str, hash: &str, cache: &Path) -> anyhow::Result<Vec<Variant>> {
+    let dir = cache_dir(cache, hash);
+    let manifest = dir.join("variants.yml");
+    if let Ok(cached) = fs::read_to_string(&manifest) {
+        if let Ok(variants) = serde_yaml::from_str(&cached) {
+            return Ok(variants);
+        }
+    }
+    let image = image::load_from_memory(bytes)?;
+    let fallback = if image.color().has_alpha() {
+        ("image/png", "png")
+    } else {
+        ("image/jpeg", "jpg")
+    };
+    let mut widths: Vec<u32> = WIDTHS.iter().copied().filter(|w| *w < image.width()).collect();
+    // browsers without webp need the original in another format
+    let webp_source = image::guess_format(bytes).ok() == Some(ImageFormat::WebP);
+    if webp_source && WIDTHS.last().is_some_and(|widest| image.width() <= *widest) {
+        widths.push(image.width());
+    }
+    fs::create_dir_all(&dir)?;
+    let mut variants = Vec::new();
+    for width in widths {
+        let resized = if width == image.width() {
+            image.clone()
+        } else {
+            let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+            image.resize_exact(width, height, FilterType::CatmullRom)
+        };
+        let fallback_bytes = encode_fallback(&resized, fallback.0)?;
+        if fallback_bytes.len() > BUDGET {
+            return Err(anyhow::Error::msg(format!(
+                "{} at {}w is {} bytes, over the {} bytes budget",
+                name,
+                width,
+                fallback_bytes.len(),
+                BUDGET
+            )));
+        }
+        // the original already is the webp of its own size
+        let webp = if width == image.width() {
+            None
+        } else {
+            encode_webp(&resized, fallback_bytes.len())?
+        };
+        if let Some(webp) = webp {
+            let file = format!("{}.{}.webp", name, width);
+            fs::write(dir.join(&file), &webp)?;
+            variants.push(Variant {
+                file,
+                width,
+                mime: "image/webp".to_owned(),
+            });
+        }
+        let file = format!("{}.{}.{}", name, width, fallback.1);
+        fs::write(dir.join(&file), &fallback_bytes)?;
+        variants.push(Variant {
+            file,
+            width,
+            mime: fallback.0.to_owned(),
+        });
+    }
+    fs::write(manifest, serde_yaml::to_string(&variants)?)?;
+    Ok(variants)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn generate() {
+        let cache = std::env::temp_dir().join(format!("fubuki-images-{}", std::process::id()));
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(1000, 500, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        }));
+        let bytes = encode(&image, "image/png", 0).unwrap();
+        let variants = variants(&bytes, "cover.00", "00", &cache).unwrap();
+        let widths: Vec<u32> = variants.iter().map(|v| v.width).collect();
+        assert!(widths.contains(&480) && widths.contains(&960));
+        assert!(!widths.contains(&1440));
+        let dir = cache_dir(&cache, "00");
+        assert!(variants.iter().all(|v| dir.join(&v.file).exists()));
+        assert!(variants.iter().any(|v| v.mime == "image/jpeg"));
+        // posterized, the gradient is smaller as webp
+        assert!(variants.iter().any(|v| v.mime == "image/webp"));
+        // second run comes from the cache
+        assert_eq!(
+            super::variants(b"not an image", "cover.00", "00", &cache).unwrap(),
+            variants
+        );
+        // a webp original gets a jpeg of its own size
+        let bytes = encode(&image, "image/webp", 0).unwrap();
+        let variants = super::variants(&bytes, "cover.01", "01", &cache).unwrap();
+        assert!(variants.iter().any(|v| v.width == 1000 && v.mime == "image/jpeg"));
+        assert!(!variants.iter().any(|v| v.width == 1000 && v.mime == "image/webp"));
+        fs::remove_dir_all(cache).unwrap();
+    }
+}
@@ -1,15 +1,47 @@
+mod assets;
 mod atom;
-mod date;
+mod bake;
+mod config;
 mod front_matter;
+mod highlight;
+mod html;
+mod images;
+mod mentions;
+#[cfg(test)]
+mod mock;
+mod og;
+mod publish;
+mod pwa;
+mod themes;
+mod webmention;
 
 use crate::atom::Post;
 use blake2::Digest;
+use fubuki_types::{colors::Scheme, markdown};
 use std::{
     env, fs,
     io::{Read, Write},
     path,
 };
 
+fn config() -> anyhow::Result<config::Config> {
+    config::Config::load(env::var("FUBUKI_CONFIG").unwrap_or_else(|_| "fubuki.yml".into()))
+}
+
+/// kept between builds
+fn cache_dir() -> path::PathBuf {
+    env::var("FUBUKI_CACHE")
+        .unwrap_or_else(|_| ".cache/index_gen".into())
+        .into()
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(30))
+        .user_agent(concat!("fubuki index_gen/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
 /// return command option
 /// return (dist, orig)
 fn read_option() -> (String, String) {
@@ -20,6 +52,13 @@ fn read_option() -> (String, String) {
     )
 }
 
+/// blake2b of a post, the client caches posts by it
+fn post_hash(bytes: &[u8]) -> String {
+    let mut hasher = blake2::Blake2b512::new();
+    hasher.update(bytes);
+    base64::encode(hasher.finalize())
+}
+
 fn file_handle(entry: &fs::DirEntry) -> anyhow::Result<Post> {
     if entry.path().is_dir() {
         return Err(anyhow::Error::msg("Not a file."));
@@ -27,15 +66,14 @@ fn file_handle(entry: &fs::DirEntry) -> anyhow::Result<Post> {
     println!("::  {}", entry.path().to_string_lossy());
     let mut file = fs::File::open(entry.path())?;
     let mut contents = String::new();
-    let mut hasher = blake2::Blake2b512::new();
     file.read_to_string(&mut contents)?;
-    hasher.update(contents.as_bytes());
-    let res = hasher.finalize();
-    let hash = base64::encode(res);
-    if let Ok((front_matter, content)) = front_matter::parse_front_matter(contents) {
+    let hash = post_hash(contents.as_bytes());
+    if let Ok((front_matter, content)) = front_matter::parse_front_matter(contents.clone()) {
         let post = front_matter.into_post(entry.file_name().to_string_lossy().replace(".md", ""), hash);
+        let raw_front_matter = contents[..contents.len() - content.len()].to_owned();
         return Ok(Post {
             front_matter: post,
+            raw_front_matter,
             content,
         });
     } else {
@@ -62,12 +100,85 @@ pub fn read_files() -> anyhow::Result<()> {
     }
     // fms sort
     posts.sort_by(|a, b| b.front_matter.url.cmp(&a.front_matter.url));
+    let config = config()?;
+    let themes = themes::load(path::Path::new(&config.theme.file))?;
+    let assets = assets::Assets::new(from, path::Path::new(&dist).join("assets"), cache_dir());
+    for post in posts.iter_mut() {
+        post.content = bake::bake(&post.content, &post.front_matter.url, &assets)?;
+    }
+    // math or shortcodes the client can not render should stop the build
+    for post in posts.iter() {
+        let errors = markdown::render_to(&post.content, markdown::Target::Web).errors;
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(anyhow::Error::msg(format!(
+                "{}: {}",
+                post.front_matter.url,
+                errors.join("; ")
+            )));
+        }
+    }
+    // only posts that got an image point to one
+    let images = og::write(
+        &posts,
+        path::Path::new(&dist),
+        &config.og,
+        &themes.preferred(Scheme::Light).colors,
+        &cache_dir(),
+    )?;
+    for post in posts.iter_mut() {
+        if let Some(image) = images.get(&post.front_matter.url) {
+            post.raw_front_matter = front_matter::with_image(&post.raw_front_matter, image);
+            post.front_matter.image = Some(image.clone());
+        }
+    }
+    // baked posts replace the copies trunk staged
+    let baked = path::Path::new(&dist).join(&orig);
+    println!("## write baked posts to {}", baked.to_string_lossy());
+    fs::create_dir_all(&baked)?;
+    // local assets are published under hashed names, the originals trunk copied go
+    for entry in fs::read_dir(&baked)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        }
+    }
+    for post in posts.iter_mut() {
+        let target = baked.join(format!("{}.md", post.front_matter.url));
+        let content = [post.raw_front_matter.as_str(), &post.content].concat();
+        // what the client fetches, so new asset names reach its cache too
+        post.front_matter.hash = post_hash(content.as_bytes());
+        fs::write(target, content)?;
+    }
+    // static pages for readers without wasm
+    html::write(path::Path::new(&dist), &posts, &config, &images)?;
+    // after the shell is final, its content versions the worker
+    pwa::write(
+        path::Path::new(&dist),
+        &config.pwa,
+        &themes.preferred(Scheme::Light).colors,
+        &config.feed.lang,
+    )?;
     // get atom xml
     let feed = [dist.clone(), "/atom.xml".into()].concat();
     println!("## write atom xml result to {}", feed);
-    let mut atom_output = fs::File::create(feed)?;
-    let atom_feed = atom::gather_posts(posts.clone());
-    atom_feed.write_to(&mut atom_output)?;
+    let atom_feeds = atom::gather_posts(posts.clone(), &config.feed, &config.author);
+    atom_feeds.current.write_to(fs::File::create(feed)?)?;
+    for (n, archive) in atom_feeds.archives.iter().enumerate() {
+        let name = [dist.as_str(), "/", &atom::archive_name(n + 1)].concat();
+        archive.write_to(fs::File::create(name)?)?;
+    }
+    println!("## write {} atom archives", atom_feeds.archives.len());
+    // received webmentions
+    let slugs: Vec<String> = posts.iter().map(|post| post.front_matter.url.clone()).collect();
+    let token = env::var(mentions::TOKEN_VAR).ok();
+    mentions::bake(
+        &slugs,
+        path::Path::new(&dist),
+        &config.mentions,
+        token.as_deref(),
+        &agent(),
+    )?;
     // get yaml
     let yaml = [dist, "/posts.yml".into()].concat();
     println!("## write post yaml result to {}", yaml);
@@ -77,3 +188,17 @@ pub fn read_files() -> anyhow::Result<()> {
     yaml_output.write_all(serde_yaml::to_string(&fms).unwrap().as_bytes())?;
     Ok(())
 }
+
+/// `index_gen publish [dist]`
+/// `FUBUKI_CHANGED` lists the post files changed since the last deploy, one per line.
+pub fn publish() -> anyhow::Result<()> {
+    let dist = env::args().nth(2).unwrap_or_else(|| "dist".into());
+    println!("publish {}", dist);
+    let changed = env::var("FUBUKI_CHANGED")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|file| path::Path::new(file.trim()).file_name()?.to_str()?.strip_suffix(".md"))
+        .map(str::to_owned)
+        .collect();
+    publish::publish(path::Path::new(&dist), &config()?, &cache_dir(), &changed)
+}
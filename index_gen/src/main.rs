@@ -1,3 +1,7 @@
 fn main() {
-    index_gen::read_files().expect("error!");
+    if std::env::args().nth(1).as_deref() == Some("publish") {
+        index_gen::publish().expect("publish failed!");
+    } else {
+        index_gen::read_files().expect("error!");
+    }
 }
@@ -0,0 +1,97 @@
+//! received webmentions, fetched at build time
+//! the webmention.io token stays on the build machine, readers get a static
+//! `mentions/<slug>.json` in the same jf2 shape.
+use crate::config;
+use fubuki_types::{head, webmention};
+use std::{fs, path::Path};
+
+/// token of the mention api
+pub const TOKEN_VAR: &str = "WEBMENTION_IO_TOKEN";
+
+fn fetch(
+    agent: &ureq::Agent,
+    config: &config::Mentions,
+    token: &str,
+    target: &str,
+) -> anyhow::Result<webmention::Feed> {
+    let json = agent
+        .get(&format!("{}/api/mentions.jf2", config.base.trim_end_matches('/')))
+        .query("target", target)
+        .query("per-page", &config.per_page.to_string())
+        .query("token", token)
+        .call()?
+        .into_string()?;
+    // only pass on the fields the client reads
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// mentions of a post, posts were linked as `/post/<slug>/` before and mentions of that count too
+fn mentions(agent: &ureq::Agent, config: &config::Mentions, token: &str, slug: &str) -> anyhow::Result<String> {
+    let url = head::post_url(slug);
+    let mut feed = fetch(agent, config, token, &url)?;
+    for mention in fetch(agent, config, token, &format!("{}/", url))?.children {
+        if !feed.children.iter().any(|m| m.url == mention.url) {
+            feed.children.push(mention);
+        }
+    }
+    Ok(serde_json::to_string(&feed)?)
+}
+
+/// write `dist/mentions/<slug>.json` for every post, nothing without a token
+pub fn bake(
+    slugs: &[String],
+    dist: &Path,
+    config: &config::Mentions,
+    token: Option<&str>,
+    agent: &ureq::Agent,
+) -> anyhow::Result<()> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        println!("::  {} not set, skip webmentions", TOKEN_VAR);
+        return Ok(());
+    };
+    let target = dist.join("mentions");
+    fs::create_dir_all(&target)?;
+    for slug in slugs {
+        match mentions(agent, config, token, slug) {
+            Ok(json) => fs::write(target.join(format!("{}.json", slug)), json)?,
+            // a flaky api should not stop the deploy, the post just shows no mentions
+            Err(e) => println!("x mentions of {}: {}", slug, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{self, Response};
+
+    #[test]
+    fn bake_json() {
+        let (base, requests) = mock::serve(|r| {
+            if r.path.contains("target=https%3A%2F%2Fblog.gyara.moe%2Fpost%2Fa&") {
+                Response::new(200).body(r#"{"type": "feed", "children": [{"url": "https://x.com/1"}]}"#)
+            } else if r.path.contains("target=https%3A%2F%2Fblog.gyara.moe%2Fpost%2Fa%2F&") {
+                Response::new(200).body(r#"{"children": [{"url": "https://x.com/1"}, {"url": "https://x.com/2"}]}"#)
+            } else {
+                Response::new(500)
+            }
+        });
+        let dist = std::env::temp_dir().join(format!("fubuki-mentions-{}", std::process::id()));
+        let config = config::Mentions {
+            base,
+            ..Default::default()
+        };
+        let slugs = ["a".to_owned(), "b".to_owned()];
+        bake(&slugs, &dist, &config, None, &ureq::Agent::new()).unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+        bake(&slugs, &dist, &config, Some("secret"), &ureq::Agent::new()).unwrap();
+        assert!(requests.lock().unwrap()[0].path.contains("token=secret"));
+        let json = fs::read_to_string(dist.join("mentions/a.json")).unwrap();
+        let feed: webmention::Feed = serde_json::from_str(&json).unwrap();
+        let urls: Vec<&str> = feed.children.iter().map(|m| m.url.as_str()).collect();
+        assert_eq!(urls, ["https://x.com/1", "https://x.com/2"]);
+        assert!(!dist.join("mentions/b.json").exists());
+        fs::remove_dir_all(dist).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+//! 1200×630 open graph images, drawn from `og.svg` with the site palette
+//! Fonts are read from `og.fonts`, which is not in the repository, `scripts/fonts.sh`
+//! downloads the ones pinned in `scripts/fonts.lock` and checks their sums.
+//! The first one by name is used and the rest fill in the chars it lacks.
+//! Without fonts no image is drawn, and posts then leave out `og:image`.
+//! Images are cached by the post hash and everything else that goes into the picture.
+use crate::{assets, atom::Post, config, html};
+use fubuki_types::{
+    colors::Colors,
+    head::{self, SITE_NAME},
+    markdown::escape,
+};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{fontdb, Options, Tree},
+};
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+const TEMPLATE: &str = include_str!("og.svg");
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+/// title lines, in em of the title font
+const LINE_WIDTH: f32 = 15.5;
+const MAX_LINES: usize = 3;
+const LINE_HEIGHT: usize = 84;
+
+/// rough advance of a char, full width or not
+fn advance(c: char) -> f32 {
+    if c.is_ascii() {
+        0.55
+    } else {
+        1.0
+    }
+}
+
+/// wrap the title by hand, svg text does not
+/// latin words are kept whole when they fit on a line.
+pub fn wrap(title: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut width = 0.0;
+    for c in title.chars() {
+        if width + advance(c) > LINE_WIDTH {
+            let rest = match line.rfind(' ') {
+                // a latin word moves to the next line whole
+                Some(space)
+                    if c.is_ascii_alphanumeric() && line[space + 1..].chars().all(|c| c.is_ascii_alphanumeric()) =>
+                {
+                    let rest = line[space + 1..].to_owned();
+                    line.truncate(space);
+                    rest
+                }
+                _ => String::new(),
+            };
+            lines.push(line.trim_end().to_owned());
+            line = rest;
+            width = line.chars().map(advance).sum();
+            if c == ' ' {
+                continue;
+            }
+        }
+        line.push(c);
+        width += advance(c);
+    }
+    if !line.trim().is_empty() {
+        lines.push(line.trim_end().to_owned());
+    }
+    if lines.len() > MAX_LINES {
+        lines.truncate(MAX_LINES);
+        let last = &mut lines[MAX_LINES - 1];
+        while last.chars().map(advance).sum::<f32>() + 1.0 > LINE_WIDTH {
+            last.pop();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+/// the svg of a post, `font` is the `font-family` list
+pub fn svg(post: &Post, font: &str, colors: &Colors) -> String {
+    let (title, _) = html::title_and_body(post);
+    let lines = wrap(&title);
+    let title: String = lines
+        .iter()
+        .enumerate()
+        .map(|(n, line)| {
+            let dy = if n == 0 { 64 } else { LINE_HEIGHT };
+            format!(r#"<tspan x="120" dy="{}">{}</tspan>"#, dy, escape(line))
+        })
+        .collect();
+    let fm = &post.front_matter;
+    TEMPLATE
+        .replace("{background}", &colors.background)
+        .replace("{brand_bg2}", &colors.brand_bg2)
+        .replace("{brand_fg1}", &colors.brand_fg1)
+        .replace("{red}", &colors.red)
+        .replace("{blue}", &colors.blue)
+        .replace("{bold}", &colors.bold)
+        .replace("{normal}", &colors.normal)
+        .replace("{code_comment}", &colors.code_comment)
+        .replace("{bar}", &(lines.len().max(1) * LINE_HEIGHT - 10).to_string())
+        .replace("{font}", &escape(font))
+        .replace("{site_name}", SITE_NAME)
+        .replace("{host}", head::SITE.trim_start_matches("https://"))
+        .replace("{category}", &escape(&fm.category))
+        .replace("{date}", &escape(&fm.date))
+        .replace("{title}", &title)
+}
+
+pub struct Painter {
+    options: Options<'static>,
+    font: String,
+    font_hash: String,
+}
+
+impl Painter {
+    /// none when there is no font in `dir`
+    pub fn new(dir: &Path) -> anyhow::Result<Option<Self>> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(None);
+        };
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        files.sort();
+        let mut db = fontdb::Database::new();
+        let mut hashes = String::new();
+        for file in files {
+            let is_font = file
+                .extension()
+                .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "ttf" | "otf" | "ttc"));
+            if is_font {
+                let bytes = fs::read(&file)?;
+                hashes.push_str(&assets::hash(&bytes));
+                db.load_font_data(bytes);
+            }
+        }
+        let mut families: Vec<String> = Vec::new();
+        for face in db.faces() {
+            if let Some((family, _)) = face.families.first() {
+                if !families.contains(family) {
+                    families.push(family.clone());
+                }
+            }
+        }
+        let Some(first) = families.first().cloned() else {
+            return Ok(None);
+        };
+        let options = Options {
+            font_family: first,
+            fontdb: Arc::new(db),
+            ..Default::default()
+        };
+        Ok(Some(Self {
+            options,
+            font: families
+                .iter()
+                .map(|family| format!("'{}'", family))
+                .collect::<Vec<_>>()
+                .join(", "),
+            font_hash: assets::hash(hashes.as_bytes()),
+        }))
+    }
+
+    pub fn png(&self, svg: &str) -> anyhow::Result<Vec<u8>> {
+        let tree = Tree::from_str(svg, &self.options)?;
+        let mut pixmap = Pixmap::new(WIDTH, HEIGHT).ok_or_else(|| anyhow::Error::msg("empty pixmap"))?;
+        resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+        Ok(pixmap.encode_png()?)
+    }
+
+    /// png of the post, from `cache` if drawn before
+    pub fn post(&self, post: &Post, colors: &Colors, cache: &Path) -> anyhow::Result<Vec<u8>> {
+        let svg = svg(post, &self.font, colors);
+        let key = assets::hash(
+            [post.front_matter.hash.as_str(), &self.font_hash, &svg]
+                .concat()
+                .as_bytes(),
+        );
+        let cached = cache.join(format!("{}.png", key));
+        if let Ok(png) = fs::read(&cached) {
+            return Ok(png);
+        }
+        let png = self.png(&svg)?;
+        fs::create_dir_all(cache)?;
+        fs::write(&cached, &png)?;
+        Ok(png)
+    }
+}
+
+/// `dist/og/<slug>.png` for every post in `colors`, slug to image url
+pub fn write(
+    posts: &[Post],
+    dist: &Path,
+    config: &config::Og,
+    colors: &Colors,
+    cache: &Path,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut images = BTreeMap::new();
+    let Some(painter) = Painter::new(Path::new(&config.fonts))? else {
+        println!("::  no font in {}, skip og images", config.fonts);
+        return Ok(images);
+    };
+    let target = dist.join("og");
+    fs::create_dir_all(&target)?;
+    for post in posts {
+        let slug = &post.front_matter.url;
+        fs::write(
+            target.join(format!("{}.png", slug)),
+            painter.post(post, colors, &cache.join("og"))?,
+        )?;
+        images.insert(slug.clone(), head::og_image(slug));
+    }
+    println!("## write {} og images", images.len());
+    Ok(images)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fubuki_types::colors::{Scheme, Themes};
+
+    #[test]
+    fn wrap_title() {
+        assert_eq!(wrap("雪風"), ["雪風"]);
+        let cjk = "あ".repeat(20);
+        assert_eq!(wrap(&cjk), ["あ".repeat(15), "あ".repeat(5)]);
+        assert_eq!(wrap("Goodbye Demon"), ["Goodbye Demon"]);
+        assert_eq!(
+            wrap("abcdefghij klmnopqrst uvwxyzab"),
+            ["abcdefghij klmnopqrst", "uvwxyzab"]
+        );
+        assert_eq!(
+            wrap("Goodbye Demon and some more words"),
+            ["Goodbye Demon and some more", "words"]
+        );
+        let long = wrap(&"あ".repeat(60));
+        assert_eq!(long.len(), MAX_LINES);
+        assert!(long[2].ends_with('…'));
+    }
+
+    #[test]
+    fn draw() {
+        let post = Post {
+            front_matter: fubuki_types::Post {
+                url: "a".to_owned(),
+                title: "a & b".to_owned(),
+                category: "Tech".to_owned(),
+                date: "令和3年4月1日".to_owned(),
+                ..Default::default()
+            },
+            raw_front_matter: String::new(),
+            content: String::new(),
+        };
+        let light = Themes::builtin().preferred(Scheme::Light).colors.clone();
+        let svg = svg(&post, "'Kosugi Maru'", &light);
+        assert!(svg.contains(r#"<tspan x="120" dy="64">a &amp; b</tspan>"#));
+        assert!(svg.contains("Tech · 令和3年4月1日"));
+        assert!(Painter::new(Path::new("no/fonts")).unwrap().is_none());
+        // shapes are enough to check the size
+        let painter = Painter {
+            options: Options::default(),
+            font: String::new(),
+            font_hash: String::new(),
+        };
+        let png = painter.png(&svg).unwrap();
+        let size = imagesize::blob_size(&png).unwrap();
+        assert_eq!((size.width, size.height), (1200, 630));
+    }
+}
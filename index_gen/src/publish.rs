@@ -0,0 +1,151 @@
+//! `index_gen publish <dist>`, tell the hub about changed feeds and send webmentions
+//! hashes of what was announced last time are kept in the cache, so an unchanged
+//! feed is not pinged again.
+use crate::{assets, atom, config, webmention};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// baked posts inside dist
+const POSTS: &str = "public/post";
+
+/// feed file name to the hash last announced
+type State = BTreeMap<String, String>;
+
+fn state_file(cache: &Path) -> PathBuf {
+    cache.join("published.yml")
+}
+
+fn load_state(cache: &Path) -> State {
+    fs::read_to_string(state_file(cache))
+        .ok()
+        .and_then(|yaml| serde_yaml::from_str(&yaml).ok())
+        .unwrap_or_default()
+}
+
+/// `atom.xml` and its archives, with their hashes
+fn feeds(dist: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let mut feeds = Vec::new();
+    for file in fs::read_dir(dist)? {
+        let name = file?.file_name().to_string_lossy().into_owned();
+        if name == "atom.xml" || (name.starts_with("atom-archive-") && name.ends_with(".xml")) {
+            let hash = assets::hash(&fs::read(dist.join(&name))?);
+            feeds.push((name, hash));
+        }
+    }
+    feeds.sort();
+    Ok(feeds)
+}
+
+/// status of a POST, non 2xx included
+fn post(agent: &ureq::Agent, url: &str, form: &[(&str, &str)]) -> anyhow::Result<u16> {
+    let response = if form.is_empty() {
+        agent.post(url).call()
+    } else {
+        agent.post(url).send_form(form)
+    };
+    match response {
+        Ok(response) => Ok(response.status()),
+        Err(ureq::Error::Status(status, _)) => Ok(status),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// print one result, true when it went through
+fn report(what: &str, feed: &str, result: anyhow::Result<u16>) -> bool {
+    match result {
+        Ok(status) if (200..300).contains(&status) => {
+            println!("<- {} {} {}", status, what, feed);
+            true
+        }
+        Ok(status) => {
+            println!("x {} {} {}", status, what, feed);
+            false
+        }
+        Err(e) => {
+            println!("x {} {}: {}", what, feed, e);
+            false
+        }
+    }
+}
+
+/// `changed` are the slugs of posts changed since the last deploy
+pub fn publish(dist: &Path, config: &config::Config, cache: &Path, changed: &BTreeSet<String>) -> anyhow::Result<()> {
+    let agent = crate::agent();
+    let mut state = load_state(cache);
+    let mut failed = 0;
+    for (name, hash) in feeds(dist)? {
+        if state.get(&name) == Some(&hash) {
+            println!("::  {} unchanged", name);
+            continue;
+        }
+        let url = format!("{}/{}", atom::SITE, name);
+        let hub = post(&agent, &config.feed.hub, &[("hub.mode", "publish"), ("hub.url", &url)]);
+        let mut ok = report(&config.feed.hub, &url, hub);
+        if let Some(endpoint) = &config.publish.webmention_app {
+            let check = format!("{}?url={}", endpoint, url);
+            ok &= report(endpoint, &url, post(&agent, &check, &[]));
+        }
+        if ok {
+            state.insert(name, hash);
+        } else {
+            failed += 1;
+        }
+    }
+    fs::create_dir_all(cache)?;
+    fs::write(state_file(cache), serde_yaml::to_string(&state)?)?;
+    if config.publish.webmentions {
+        webmention::send(&dist.join(POSTS), cache, changed, &agent)?;
+    }
+    if failed > 0 {
+        return Err(anyhow::Error::msg(format!("{} feeds failed to publish", failed)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{self, Response};
+
+    #[test]
+    fn ping_changed() {
+        let (base, requests) = mock::serve(|r| match r.path.as_str() {
+            "/hub" => Response::new(204),
+            path if path.starts_with("/check/") => Response::new(202),
+            _ => Response::new(404),
+        });
+        let root = std::env::temp_dir().join(format!("fubuki-publish-{}", std::process::id()));
+        let (dist, cache) = (root.join("dist"), root.join("cache"));
+        fs::create_dir_all(&dist).unwrap();
+        fs::write(dist.join("atom.xml"), "a").unwrap();
+        fs::write(dist.join("atom-archive-1.xml"), "b").unwrap();
+        fs::write(dist.join("posts.yml"), "c").unwrap();
+        let mut config = config::Config::default();
+        config.publish.webmentions = false;
+        config.feed.hub = format!("{}/hub", base);
+        config.publish.webmention_app = Some(format!("{}/check/", base));
+        publish(&dist, &config, &cache, &BTreeSet::new()).unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 4);
+            assert_eq!(requests[0].method, "POST");
+            assert!(requests[0].body.contains("hub.mode=publish"));
+            assert!(requests[0].body.contains("atom-archive-1.xml"));
+            assert_eq!(requests[3].path, "/check/?url=https://blog.gyara.moe/atom.xml");
+        }
+        // only the changed feed is pinged again
+        fs::write(dist.join("atom.xml"), "d").unwrap();
+        publish(&dist, &config, &cache, &BTreeSet::new()).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 6);
+        // failures are reported and retried next time
+        config.feed.hub = format!("{}/gone", base);
+        config.publish.webmention_app = None;
+        fs::write(dist.join("atom.xml"), "e").unwrap();
+        assert!(publish(&dist, &config, &cache, &BTreeSet::new()).is_err());
+        assert_eq!(load_state(&cache).get("atom.xml"), Some(&assets::hash(b"d")));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+//! web app manifest and service worker, for installing the site and reading offline
+//! Icons are drawn from the square svg on the page background, inside the maskable safe
+//! zone. The worker precaches the shell trunk built, its version follows their content.
+use crate::{assets, config, themes};
+use fubuki_types::{
+    colors::Colors,
+    head::{DESCRIPTION, SITE_NAME},
+};
+use resvg::{
+    tiny_skia::{Color, Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+use serde_json::json;
+use std::{fs, path::Path};
+
+const WORKER: &str = include_str!("sw.js");
+pub const SIZES: [u32; 2] = [192, 512];
+/// share of the icon the drawing takes, maskable icons keep to the middle 80%
+const SAFE_ZONE: f32 = 0.8;
+/// files of the shell, besides `index.html`
+const SHELL: &[&str] = &["js", "wasm", "css", "ico"];
+
+pub fn icon_name(size: u32) -> String {
+    format!("icon-{}.png", size)
+}
+
+pub fn manifest(colors: &Colors, lang: &str) -> String {
+    let icons: Vec<_> = SIZES
+        .iter()
+        .map(|size| {
+            json!({
+                "src": format!("/{}", icon_name(*size)),
+                "sizes": format!("{0}x{0}", size),
+                "type": "image/png",
+                "purpose": "any maskable",
+            })
+        })
+        .collect();
+    let manifest = json!({
+        "name": SITE_NAME,
+        "short_name": SITE_NAME,
+        "description": DESCRIPTION,
+        "lang": lang,
+        "start_url": "/",
+        "scope": "/",
+        "display": "standalone",
+        "background_color": colors.page_bg,
+        "theme_color": colors.page_bg,
+        "icons": icons,
+    });
+    serde_json::to_string_pretty(&manifest).unwrap()
+}
+
+/// the svg centered on `background`
+pub fn icon(svg: &str, size: u32, background: &str) -> anyhow::Result<Vec<u8>> {
+    let tree = Tree::from_str(svg, &Options::default())?;
+    let mut pixmap = Pixmap::new(size, size).ok_or_else(|| anyhow::Error::msg("empty pixmap"))?;
+    let [r, g, b] = themes::parse(background).ok_or_else(|| anyhow::Error::msg("background is not a color"))?;
+    pixmap.fill(Color::from_rgba8(r, g, b, 255));
+    let view = tree.size();
+    let scale = size as f32 * SAFE_ZONE / view.width().max(view.height());
+    let (x, y) = (
+        (size as f32 - view.width() * scale) / 2.0,
+        (size as f32 - view.height() * scale) / 2.0,
+    );
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale).post_translate(x, y),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap.encode_png()?)
+}
+
+/// urls of the shell in `dist`
+pub fn precache(dist: &Path) -> anyhow::Result<Vec<String>> {
+    let mut files = vec!["/index.html".to_owned(), "/manifest.webmanifest".to_owned()];
+    // palettes the client loads at start
+    if dist.join("public/themes.yml").is_file() {
+        files.push("/public/themes.yml".to_owned());
+    }
+    let mut shell: Vec<String> = fs::read_dir(dist)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| SHELL.contains(&ext.to_string_lossy().as_ref()))
+        })
+        .filter_map(|path| Some(format!("/{}", path.file_name()?.to_string_lossy())))
+        .collect();
+    shell.sort();
+    files.extend(shell);
+    files.extend(SIZES.iter().map(|size| format!("/{}", icon_name(*size))));
+    Ok(files)
+}
+
+pub fn worker(precache: &[String], version: &str) -> String {
+    WORKER
+        .replace("{version}", version)
+        .replace("{precache}", &serde_json::to_string(precache).unwrap())
+}
+
+/// `manifest.webmanifest`, its icons and `sw.js` into `dist`
+pub fn write(dist: &Path, config: &config::Pwa, colors: &Colors, lang: &str) -> anyhow::Result<()> {
+    if !dist.join("index.html").is_file() {
+        println!("::  no index.html in {}, skip service worker", dist.to_string_lossy());
+        return Ok(());
+    }
+    let svg = fs::read_to_string(&config.icon)?;
+    for size in SIZES {
+        fs::write(dist.join(icon_name(size)), icon(&svg, size, &colors.page_bg)?)?;
+    }
+    fs::write(dist.join("manifest.webmanifest"), manifest(colors, lang))?;
+    let files = precache(dist)?;
+    let mut content = Vec::new();
+    for file in &files {
+        content.extend(fs::read(dist.join(file.trim_start_matches('/')))?);
+    }
+    fs::write(dist.join("sw.js"), worker(&files, &assets::hash(&content)))?;
+    println!("## write service worker, {} files in the shell", files.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fubuki_types::colors::{Scheme, Themes};
+
+    #[test]
+    fn app() {
+        let light = Themes::builtin().preferred(Scheme::Light).colors.clone();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest(&light, "zh-Hans")).unwrap();
+        assert_eq!(manifest["name"], SITE_NAME);
+        assert_eq!(manifest["theme_color"], "#ffffff");
+        assert_eq!(manifest["icons"][1]["src"], "/icon-512.png");
+        assert_eq!(manifest["icons"][1]["sizes"], "512x512");
+        let svg = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../public/icon/square.svg")).unwrap();
+        let png = icon(&svg, 192, &light.page_bg).unwrap();
+        let size = imagesize::blob_size(&png).unwrap();
+        assert_eq!((size.width, size.height), (192, 192));
+        let worker = worker(&["/index.html".to_owned(), "/app.wasm".to_owned()], "v1");
+        assert!(worker.contains(r#"const VERSION = "v1";"#));
+        assert!(worker.contains(r#"const PRECACHE = ["/index.html","/app.wasm"];"#));
+        assert!(worker.contains("fubuki-shell-${VERSION}"));
+    }
+}
@@ -0,0 +1,66 @@
+// written by index_gen, the shell is cached on install and posts as they are read
+const VERSION = "{version}";
+const SHELL = `fubuki-shell-${VERSION}`;
+const POSTS = "fubuki-sw-posts";
+const PRECACHE = {precache};
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(SHELL)
+      .then((cache) => cache.addAll(PRECACHE))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+// shells of older builds go
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith("fubuki-shell-") && key !== SHELL)
+            .map((key) => caches.delete(key)),
+        )
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+// answer from the cache at once, and refresh it for the next visit
+const staleWhileRevalidate = async (event) => {
+  const cache = await caches.open(POSTS);
+  const cached = await cache.match(event.request);
+  const fresh = fetch(event.request).then((response) => {
+    if (response.ok) {
+      cache.put(event.request, response.clone());
+    }
+    return response;
+  });
+  if (cached) {
+    event.waitUntil(fresh.catch(() => undefined));
+    return cached;
+  }
+  return fresh;
+};
+
+const isPost = (path) =>
+  path.startsWith("/public/post/") || path.startsWith("/mentions/") || path === "/posts.yml";
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  const url = new URL(request.url);
+  if (request.method !== "GET" || url.origin !== self.location.origin) {
+    return;
+  }
+  if (request.mode === "navigate") {
+    // the static page when online, the shell renders any route offline
+    event.respondWith(fetch(request).catch(() => caches.match("/index.html")));
+  } else if (isPost(url.pathname)) {
+    event.respondWith(staleWhileRevalidate(event));
+  } else {
+    event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+  }
+});
@@ -0,0 +1,176 @@
+//! palettes of `public/themes.yml`, checked for WCAG contrast before they ship
+//! Text roles must reach AA, 4.5:1, a failure stops the build. Accents and code colors
+//! are only reported below 3:1, the large text and ui component level.
+use fubuki_types::colors::{Colors, Palette, Themes};
+use std::{fmt, fs, path::Path};
+
+/// AA for normal text
+pub const TEXT: f64 = 4.5;
+/// AA for large text and ui components
+pub const ACCENT: f64 = 3.0;
+
+/// text on background roles that must be readable
+const TEXT_PAIRS: &[(&str, &str)] = &[
+    ("normal", "page_bg"),
+    ("bold", "page_bg"),
+    ("normal", "background"),
+    ("normal", "bg2"),
+    ("normal", "underground"),
+    ("rev_fg", "rev_bg"),
+];
+
+const ACCENT_PAIRS: &[(&str, &str)] = &[
+    ("red", "page_bg"),
+    ("blue", "page_bg"),
+    ("colors_fg", "red_bg"),
+    ("colors_fg", "green_bg"),
+    ("colors_fg", "blue_bg"),
+    ("code_comment", "underground"),
+    ("code_keyword", "underground"),
+    ("code_string", "underground"),
+    ("code_constant", "underground"),
+    ("code_function", "underground"),
+    ("code_type", "underground"),
+];
+
+/// `#rgb` or `#rrggbb`
+pub fn parse(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        3 => {
+            let mut rgb = [0; 3];
+            for (n, c) in hex.chars().enumerate() {
+                rgb[n] = channel(&c.to_string())? * 17;
+            }
+            Some(rgb)
+        }
+        6 => Some([channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?]),
+        _ => None,
+    }
+}
+
+/// relative luminance, WCAG 2.x
+fn luminance([r, g, b]: [u8; 3]) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+pub fn contrast(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    let (light, dark) = if a > b { (a, b) } else { (b, a) };
+    (light + 0.05) / (dark + 0.05)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub palette: String,
+    pub fg: &'static str,
+    pub bg: &'static str,
+    /// none when a color can not be read
+    pub ratio: Option<f64>,
+    pub required: f64,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ratio {
+            Some(ratio) => write!(
+                f,
+                "{}: {} on {} is {:.2}:1, below {}:1",
+                self.palette, self.fg, self.bg, ratio, self.required
+            ),
+            None => write!(f, "{}: {} or {} is not a #rrggbb color", self.palette, self.fg, self.bg),
+        }
+    }
+}
+
+fn check_pairs(palette: &Palette, pairs: &[(&'static str, &'static str)], required: f64) -> Vec<Problem> {
+    let colors: &Colors = &palette.colors;
+    pairs
+        .iter()
+        .filter_map(|&(fg, bg)| {
+            let ratio = match (colors.role(fg).and_then(parse), colors.role(bg).and_then(parse)) {
+                (Some(a), Some(b)) => Some(contrast(a, b)),
+                _ => None,
+            };
+            if ratio.is_some_and(|ratio| ratio >= required) {
+                return None;
+            }
+            Some(Problem {
+                palette: palette.name.clone(),
+                fg,
+                bg,
+                ratio,
+                required,
+            })
+        })
+        .collect()
+}
+
+/// text problems and accent problems of every palette
+pub fn check(themes: &Themes) -> (Vec<Problem>, Vec<Problem>) {
+    let mut text = Vec::new();
+    let mut accent = Vec::new();
+    for palette in &themes.0 {
+        text.extend(check_pairs(palette, TEXT_PAIRS, TEXT));
+        accent.extend(check_pairs(palette, ACCENT_PAIRS, ACCENT));
+    }
+    (text, accent)
+}
+
+/// read and check the palettes, unreadable text stops the build
+pub fn load(path: &Path) -> anyhow::Result<Themes> {
+    let themes: Themes = serde_yaml::from_str(&fs::read_to_string(path)?)?;
+    if themes.0.is_empty() {
+        return Err(anyhow::Error::msg(format!("no palette in {}", path.to_string_lossy())));
+    }
+    let (text, accent) = check(&themes);
+    for problem in &accent {
+        println!("::  {}", problem);
+    }
+    if !text.is_empty() {
+        let text: Vec<String> = text.iter().map(ToString::to_string).collect();
+        return Err(anyhow::Error::msg(text.join("; ")));
+    }
+    Ok(themes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ratio() {
+        assert_eq!(parse("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse("#86C166"), Some([0x86, 0xc1, 0x66]));
+        assert_eq!(parse("red"), None);
+        let black_white = contrast([0, 0, 0], [255, 255, 255]);
+        assert!((black_white - 21.0).abs() < 1e-9);
+        assert!((contrast([0x77, 0x77, 0x77], [255, 255, 255]) - 4.48).abs() < 0.01);
+    }
+
+    #[test]
+    fn shipped() {
+        let themes = load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../public/themes.yml"))).unwrap();
+        assert_eq!(themes, Themes::builtin());
+        // the high contrast preset keeps to AAA everywhere
+        let high = themes.get("high-contrast").unwrap();
+        let all: Vec<_> = TEXT_PAIRS.iter().chain(ACCENT_PAIRS).copied().collect();
+        assert_eq!(check_pairs(high, &all, 7.0), []);
+        let mut broken = themes.get("light").unwrap().clone();
+        broken.colors.normal = "#eeeeee".to_owned();
+        let problems = check_pairs(&broken, TEXT_PAIRS, TEXT);
+        assert_eq!(problems[0].fg, "normal");
+        assert!(problems[0]
+            .to_string()
+            .starts_with("light: normal on page_bg is 1.16:1"));
+    }
+}
@@ -0,0 +1,328 @@
+//! outgoing webmentions
+//! every external link of a post is told about the post, the state file remembers
+//! what was sent, so only links added to or removed from a post are told again.
+use crate::{atom, front_matter};
+use fubuki_types::{
+    head,
+    markdown::{self, shortcode, Document},
+};
+use pulldown_cmark::{Event, Tag};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// what was sent for one post
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+struct Sent {
+    targets: BTreeSet<String>,
+}
+
+/// post url to what was sent
+type State = BTreeMap<String, Sent>;
+
+fn state_file(cache: &Path) -> PathBuf {
+    cache.join("webmentions.yml")
+}
+
+/// external links of a post
+pub fn links(md: &str) -> BTreeSet<String> {
+    let (md, _) = shortcode::expand(md, markdown::Target::Web);
+    Document::parse(&md)
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.split('#').next().unwrap_or_default()),
+            _ => None,
+        })
+        .filter(|url| (url.starts_with("https://") || url.starts_with("http://")) && !url.starts_with(atom::SITE))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// attributes of one tag, names lowercased
+fn attributes(tag: &str) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let mut rest = tag.trim_start_matches(|c: char| c != ' ' && c != '\t' && c != '\n');
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        if end == 0 {
+            break;
+        }
+        let name = rest[..end].to_lowercase();
+        rest = rest[end..].trim_start();
+        let value = if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (value, len) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let close = after[1..].find(quote).map_or(after.len(), |i| i + 1);
+                    (&after[1..close], (close + 1).min(after.len()))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], end)
+                }
+            };
+            rest = &after[len..];
+            value
+        } else {
+            ""
+        };
+        attributes.insert(name, value.to_owned());
+    }
+    attributes
+}
+
+fn is_webmention(rel: &str) -> bool {
+    rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("webmention"))
+}
+
+/// `<https://example.com/wm>; rel="webmention"`
+fn from_link_header(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let rel = params.split(';').find_map(|p| {
+            let (name, value) = p.split_once('=')?;
+            (name.trim() == "rel").then(|| value.trim().trim_matches('"'))
+        })?;
+        is_webmention(rel).then(|| url.trim().trim_start_matches('<').trim_end_matches('>'))
+    })
+}
+
+/// first `<link>` or `<a>` with `rel=webmention`
+fn from_html(html: &str) -> Option<String> {
+    // ascii only, offsets in it are offsets in `html`
+    let lower = html.to_ascii_lowercase();
+    let mut at = 0;
+    while let Some(i) = lower[at..].find('<') {
+        let start = at + i;
+        let end = lower[start..].find('>').map_or(lower.len(), |e| start + e);
+        let tag = &html[start + 1..end];
+        let name = lower[start + 1..end].split_whitespace().next().unwrap_or_default();
+        if name == "link" || name == "a" {
+            let attributes = attributes(tag);
+            if let (Some(rel), Some(href)) = (attributes.get("rel"), attributes.get("href")) {
+                if is_webmention(rel) {
+                    return Some(href.clone());
+                }
+            }
+        }
+        at = end;
+    }
+    None
+}
+
+/// webmention endpoint of `target`, relative endpoints resolved
+pub fn discover(agent: &ureq::Agent, target: &str) -> anyhow::Result<Option<String>> {
+    let response = agent.get(target).call()?;
+    let base = Url::parse(response.get_url())?;
+    let endpoint = response
+        .all("link")
+        .into_iter()
+        .find_map(from_link_header)
+        .map(str::to_owned);
+    let endpoint = match endpoint {
+        Some(endpoint) => Some(endpoint),
+        None if response.content_type().contains("html") => from_html(&response.into_string()?),
+        None => None,
+    };
+    Ok(match endpoint {
+        Some(endpoint) => Some(base.join(&endpoint)?.to_string()),
+        None => None,
+    })
+}
+
+/// discover and send, `Ok(false)` when the target takes no webmentions
+fn send_one(agent: &ureq::Agent, source: &str, target: &str) -> anyhow::Result<bool> {
+    let endpoint = match discover(agent, target) {
+        Ok(Some(endpoint)) => endpoint,
+        Ok(None) => return Ok(false),
+        // a target gone for good needs no mention
+        Err(e) => match e.downcast_ref::<ureq::Error>() {
+            Some(ureq::Error::Status(404 | 410, _)) => return Ok(false),
+            _ => return Err(e),
+        },
+    };
+    let status = match agent
+        .post(&endpoint)
+        .send_form(&[("source", source), ("target", target)])
+    {
+        Ok(response) => response.status(),
+        // the receiver refused it, sending again will not help
+        Err(ureq::Error::Status(status, _)) if status < 500 => status,
+        Err(e) => return Err(e.into()),
+    };
+    println!("<- {} webmention {} -> {}", status, source, target);
+    Ok(true)
+}
+
+/// send webmentions for the baked posts in `posts`
+/// without a state file only the posts in `changed` are sent, the rest count as sent.
+/// A target that failed is tried again next time, failures never fail the deploy.
+pub fn send(posts: &Path, cache: &Path, changed: &BTreeSet<String>, agent: &ureq::Agent) -> anyhow::Result<()> {
+    let old: Option<State> = fs::read_to_string(state_file(cache))
+        .ok()
+        .and_then(|yaml| serde_yaml::from_str::<State>(&yaml).ok())
+        .map(|old| {
+            old.into_iter()
+                // older states kept urls with a trailing slash
+                .map(|(source, sent)| (source.trim_end_matches('/').to_owned(), sent))
+                .collect()
+        });
+    let mut now = BTreeMap::new();
+    for file in fs::read_dir(posts)? {
+        let file = file?;
+        let name = file.file_name().to_string_lossy().into_owned();
+        let Some(slug) = name.strip_suffix(".md") else {
+            continue;
+        };
+        if slug.contains("test-page") {
+            continue;
+        }
+        let contents = fs::read_to_string(file.path())?;
+        let content = front_matter::parse_front_matter(contents.clone())
+            .map(|(_, content)| content)
+            .unwrap_or(contents);
+        let sent = Sent {
+            targets: links(&content),
+        };
+        now.insert(head::post_url(slug), sent);
+    }
+    let old = old.unwrap_or_else(|| {
+        println!(
+            "::  no webmention state, send for the {} changed posts only",
+            changed.len()
+        );
+        let changed: BTreeSet<String> = changed.iter().map(|slug| head::post_url(slug)).collect();
+        now.iter()
+            .filter(|(source, _)| !changed.contains(*source))
+            .map(|(source, sent)| (source.clone(), sent.clone()))
+            .collect()
+    });
+    let mut state = State::new();
+    let mut failed = 0;
+    let sources: BTreeSet<&String> = old.keys().chain(now.keys()).collect();
+    for source in sources {
+        let (before, after) = (old.get(source), now.get(source));
+        // added and removed links, a deleted post tells all of its links
+        let (before_targets, after_targets) = (
+            before.map(|sent| sent.targets.clone()).unwrap_or_default(),
+            after.map(|sent| sent.targets.clone()).unwrap_or_default(),
+        );
+        let mut targets = after_targets.clone();
+        for target in before_targets.symmetric_difference(&after_targets) {
+            if let Err(e) = send_one(agent, source, target) {
+                println!("x webmention {} -> {}: {}", source, target, e);
+                failed += 1;
+                // keep the change pending so the next run tells it again
+                if !targets.remove(target) {
+                    targets.insert(target.clone());
+                }
+            }
+        }
+        if after.is_some() || !targets.is_empty() {
+            state.insert(source.clone(), Sent { targets });
+        }
+    }
+    fs::create_dir_all(cache)?;
+    fs::write(state_file(cache), serde_yaml::to_string(&state)?)?;
+    if failed > 0 {
+        println!("x {} webmentions failed, tried again next time", failed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{self, Response};
+
+    #[test]
+    fn external_links() {
+        let links = links("[a](https://a.com/x#y) [self](https://blog.gyara.moe/) [rel](./b) `[c](https://c.com)`\n");
+        assert_eq!(links.into_iter().collect::<Vec<_>>(), ["https://a.com/x"]);
+    }
+
+    #[test]
+    fn endpoint() {
+        assert_eq!(
+            from_link_header(r#"<https://a.com/>; rel="me", </wm>; rel="webmention""#),
+            Some("/wm")
+        );
+        assert_eq!(from_link_header("</wm>; rel=webmention"), Some("/wm"));
+        assert_eq!(from_link_header("</x>; rel=\"not-webmention\""), None);
+        let html = r#"<head><LINK href="/wm" rel="webmention"></head>"#;
+        assert_eq!(from_html(html).as_deref(), Some("/wm"));
+        let html = r#"<a href='/x'>x</a> <a rel="nofollow webmention" href="">y</a>"#;
+        assert_eq!(from_html(html).as_deref(), Some(""));
+        assert_eq!(from_html("<p rel=webmention href=/x>"), None);
+        // lowercasing `İ` would change byte offsets
+        let html = r#"<p>İİİ</p><link rel="webmention" href="/wm">"#;
+        assert_eq!(from_html(html).as_deref(), Some("/wm"));
+    }
+
+    #[test]
+    fn send_new_and_deleted() {
+        let (base, requests) = mock::serve(|r| match r.path.as_str() {
+            "/a" => Response::new(200).header("Link", "</wm>; rel=\"webmention\""),
+            "/b" => Response::new(200)
+                .header("Content-Type", "text/html")
+                .body(r#"<html><link rel="webmention" href="wm"></html>"#),
+            "/none" => Response::new(200).header("Content-Type", "text/html").body("<p>hi</p>"),
+            "/flaky" => Response::new(200).header("Link", "</broken>; rel=\"webmention\""),
+            "/wm" => Response::new(202),
+            "/broken" => Response::new(503),
+            _ => Response::new(404),
+        });
+        let root = std::env::temp_dir().join(format!("fubuki-webmention-{}", std::process::id()));
+        let (posts, cache) = (root.join("posts"), root.join("cache"));
+        fs::create_dir_all(&posts).unwrap();
+        let post = |links: &[&str]| {
+            let body: Vec<String> = links.iter().map(|l| format!("[{0}]({1}/{0})", l, base)).collect();
+            fs::write(posts.join("p.md"), body.join(" ")).unwrap();
+        };
+        let sent = || -> Vec<String> {
+            let requests = requests.lock().unwrap();
+            requests
+                .iter()
+                .filter(|r| r.method == "POST")
+                .map(|r| r.body.clone())
+                .collect()
+        };
+        let agent = ureq::Agent::new();
+        let changed = BTreeSet::from(["p".to_owned()]);
+        // the first run sends nothing for posts not changed
+        post(&["a"]);
+        send(&posts, &cache, &BTreeSet::new(), &agent).unwrap();
+        assert!(sent().is_empty());
+        fs::remove_dir_all(&cache).unwrap();
+        post(&["a", "b", "none"]);
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 2);
+        assert!(sent()[0].contains("source=https%3A%2F%2Fblog.gyara.moe%2Fpost%2Fp&"));
+        // nothing changed, nothing sent
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 2);
+        // an edit keeping the links sends nothing
+        post(&["none", "b", "a"]);
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 2);
+        // only the removed link is told
+        post(&["a"]);
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 3);
+        assert!(sent()[2].contains("%2Fb"));
+        // a failing target does not fail the run, and only it is tried again
+        post(&["a", "b", "flaky"]);
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 5);
+        send(&posts, &cache, &changed, &agent).unwrap();
+        assert_eq!(sent().len(), 6);
+        assert!(sent()[5].contains("%2Fflaky"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
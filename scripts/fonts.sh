@@ -0,0 +1,20 @@
+#!/bin/sh
+# download the fonts in fonts.lock into fonts/ and check them
+# a font that does not match its sum is left out, it never fails the build
+set -u
+lock="$(dirname "$0")/fonts.lock"
+rev=$(awk '$1 == "rev" { print $2 }' "$lock")
+mkdir -p fonts
+if ! echo "$rev" | grep -Eq '^[0-9a-f]{40}$'; then
+  echo "::warning::og fonts are not pinned in $lock, no og image is drawn"
+  exit 0
+fi
+grep -Ev '^(#|rev )' "$lock" | while read -r sum name path; do
+  if curl -sSfL -o "fonts/$name" "https://raw.githubusercontent.com/google/fonts/$rev/$path" &&
+    echo "$sum  fonts/$name" | sha256sum -c --quiet -; then
+    echo "::  font $name"
+  else
+    echo "::warning::font $name failed to download or verify, left out"
+    rm -f "fonts/$name"
+  fi
+done
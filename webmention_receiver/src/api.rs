@@ -0,0 +1,135 @@
+//! the http side, `POST /webmention` and `GET /api/mentions.jf2`
+use crate::{
+    store::Store,
+    verify::{self, Job},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use fubuki_types::webmention::Mention;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use url::Url;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<Store>,
+    pub jobs: mpsc::Sender<Job>,
+    /// hosts we take mentions for
+    pub hosts: Arc<Vec<String>>,
+    /// asked from api readers when set
+    pub token: Option<Arc<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct Received {
+    source: String,
+    target: String,
+}
+
+/// the synchronous checks of the spec, the rest happens in [`crate::verify`]
+pub fn check(source: &str, target: &str, hosts: &[String]) -> Result<Job, &'static str> {
+    let source = Url::parse(source).map_err(|_| "source is not a url")?;
+    let target = Url::parse(target).map_err(|_| "target is not a url")?;
+    if !matches!(source.scheme(), "http" | "https") || !matches!(target.scheme(), "http" | "https") {
+        return Err("only http and https urls");
+    }
+    if !verify::is_public_host(&source) {
+        return Err("source is not a public host");
+    }
+    if source == target {
+        return Err("source and target are the same");
+    }
+    if !hosts.iter().any(|host| target.host_str() == Some(host.as_str())) {
+        return Err("target is not on this site");
+    }
+    Ok(Job { source, target })
+}
+
+async fn receive(State(state): State<AppState>, Form(received): Form<Received>) -> (StatusCode, &'static str) {
+    let job = match check(&received.source, &received.target, &state.hosts) {
+        Ok(job) => job,
+        Err(reason) => return (StatusCode::BAD_REQUEST, reason),
+    };
+    match state.jobs.try_send(job) {
+        Ok(()) => (StatusCode::ACCEPTED, "accepted, the source will be checked shortly"),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "too busy, try again later"),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MentionsQuery {
+    target: String,
+    token: Option<String>,
+    #[serde(rename = "per-page")]
+    per_page: Option<usize>,
+    page: Option<usize>,
+}
+
+/// one page of the feed, in the shape of webmention.io
+pub fn feed(mentions: Vec<Mention>, per_page: usize, page: usize) -> serde_json::Value {
+    let children: Vec<Mention> = mentions.into_iter().skip(per_page * page).take(per_page).collect();
+    serde_json::json!({
+        "type": "feed",
+        "name": "Webmentions",
+        "children": children,
+    })
+}
+
+async fn mentions(
+    State(state): State<AppState>,
+    Query(query): Query<MentionsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if let Some(token) = state.token.as_deref() {
+        if query.token.as_ref() != Some(token) {
+            return Err((StatusCode::UNAUTHORIZED, "bad token".to_owned()));
+        }
+    }
+    let mentions = state
+        .store
+        .mentions(&query.target)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 1000);
+    Ok(Json(feed(mentions, per_page, query.page.unwrap_or(0))))
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/webmention", post(receive))
+        .route("/api/mentions.jf2", get(mentions))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checks() {
+        let hosts = vec!["blog.gyara.moe".to_owned()];
+        assert!(check("https://a.com/1", "https://blog.gyara.moe/post/a/", &hosts).is_ok());
+        assert!(check("ftp://a.com/1", "https://blog.gyara.moe/post/a/", &hosts).is_err());
+        assert!(check("https://a.com/1", "https://other.com/", &hosts).is_err());
+        assert!(check("https://blog.gyara.moe/", "https://blog.gyara.moe/", &hosts).is_err());
+        assert!(check("not a url", "https://blog.gyara.moe/", &hosts).is_err());
+        assert!(check("http://127.0.0.1:8080/", "https://blog.gyara.moe/post/a/", &hosts).is_err());
+    }
+
+    #[test]
+    fn paging() {
+        let mentions: Vec<Mention> = (0..5)
+            .map(|n| Mention {
+                url: n.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let page = feed(mentions, 2, 2);
+        assert_eq!(page["type"], "feed");
+        assert_eq!(page["children"].as_array().unwrap().len(), 1);
+        assert_eq!(page["children"][0]["url"], "4");
+    }
+}
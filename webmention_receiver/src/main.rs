@@ -0,0 +1,54 @@
+//! a W3C webmention receiver, standing in for webmention.io
+//! `POST /webmention` takes mentions, the source is checked in the background and
+//! what it says is kept in an embedded database. `GET /api/mentions.jf2?target=`
+//! answers in the same jf2 shape, so index_gen only needs `mentions.base` pointed here
+//! and `index.html` its `<link rel="webmention">`.
+//!
+//! settings come from the environment:
+//! - `WEBMENTION_LISTEN`, `127.0.0.1:8080`
+//! - `WEBMENTION_DB`, `mentions.redb`
+//! - `WEBMENTION_HOSTS`, comma separated, `blog.gyara.moe`
+//! - `WEBMENTION_TOKEN`, asked from api readers when set
+mod api;
+mod mf2;
+mod store;
+mod verify;
+
+use std::{env, sync::Arc};
+use tokio::sync::mpsc;
+
+fn var(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or_else(|_| default.to_owned())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let store = Arc::new(store::Store::open(var("WEBMENTION_DB", "mentions.redb"))?);
+    let (jobs, mut queue) = mpsc::channel::<verify::Job>(256);
+    let client = verify::client()?;
+    let worker_store = store.clone();
+    tokio::spawn(async move {
+        while let Some(job) = queue.recv().await {
+            match verify::verify(&client, &worker_store, &job).await {
+                Ok(verify::Outcome::Saved) => println!("<- {} -> {}", job.source, job.target),
+                Ok(verify::Outcome::Deleted) => println!("x  {} -> {} removed", job.source, job.target),
+                Err(e) => println!("x  {} -> {}: {}", job.source, job.target, e),
+            }
+        }
+    });
+    let hosts = var("WEBMENTION_HOSTS", "blog.gyara.moe")
+        .split(',')
+        .map(|host| host.trim().to_owned())
+        .collect();
+    let state = api::AppState {
+        store,
+        jobs,
+        hosts: Arc::new(hosts),
+        token: env::var("WEBMENTION_TOKEN").ok().map(Arc::new),
+    };
+    let listen = var("WEBMENTION_LISTEN", "127.0.0.1:8080");
+    println!("listen on {}", listen);
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    axum::serve(listener, api::router(state)).await?;
+    Ok(())
+}
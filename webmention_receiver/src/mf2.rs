@@ -0,0 +1,188 @@
+//! just enough microformats2 to turn a source page into a mention
+//! the first `h-entry` is the mention, its `p-author` `h-card` the author.
+use fubuki_types::webmention::{Author, Content, Mention, Property};
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+fn selector(s: &str) -> Selector {
+    Selector::parse(s).unwrap()
+}
+
+fn has_class(element: &ElementRef, class: &str) -> bool {
+    element.value().classes().any(|c| c == class)
+}
+
+fn text(element: &ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join("")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// first descendant with `class`, the element itself included
+fn find<'a>(root: &ElementRef<'a>, class: &str) -> Option<ElementRef<'a>> {
+    if has_class(root, class) {
+        return Some(*root);
+    }
+    root.select(&selector(&format!(".{}", class))).next()
+}
+
+fn absolute(base: &Url, href: &str) -> String {
+    base.join(href).map(|u| u.to_string()).unwrap_or_default()
+}
+
+/// `u-*` value, `href`, `src` or the text
+fn u_value(element: &ElementRef, base: &Url) -> String {
+    let value = element.value();
+    match value.attr("href").or_else(|| value.attr("src")) {
+        Some(href) => absolute(base, href),
+        None => text(element),
+    }
+}
+
+/// `u-in-reply-to` and friends may be a plain link or an embedded `h-cite`
+fn u_urls(root: &ElementRef, class: &str, base: &Url) -> Vec<String> {
+    root.select(&selector(&format!(".{}", class)))
+        .map(|element| {
+            if has_class(&element, "h-cite") {
+                if let Some(url) = find(&element, "u-url") {
+                    return u_value(&url, base);
+                }
+            }
+            u_value(&element, base)
+        })
+        .collect()
+}
+
+fn author(entry: &ElementRef, base: &Url) -> Author {
+    let Some(card) = find(entry, "p-author").or_else(|| find(entry, "h-card")) else {
+        return Author::default();
+    };
+    if !has_class(&card, "h-card") {
+        return Author {
+            name: text(&card),
+            url: card.value().attr("href").map(|h| absolute(base, h)).unwrap_or_default(),
+            ..Default::default()
+        };
+    }
+    Author {
+        name: find(&card, "p-name").map(|n| text(&n)).unwrap_or_else(|| text(&card)),
+        photo: find(&card, "u-photo").map(|p| u_value(&p, base)).unwrap_or_default(),
+        // an `<a class="h-card">` implies its own href
+        url: find(&card, "u-url")
+            .map(|u| u_value(&u, base))
+            .or_else(|| card.value().attr("href").map(|h| absolute(base, h)))
+            .unwrap_or_default(),
+    }
+}
+
+/// what `source` says about `target`
+pub fn parse(html: &str, source: &Url, target: &str) -> Mention {
+    let document = Html::parse_document(html);
+    let root = document.root_element();
+    let entry = root.select(&selector(".h-entry")).next().unwrap_or(root);
+    let is = |class: &str| u_urls(&entry, class, source).iter().any(|u| u == target);
+    let wm_property = if is("u-like-of") {
+        Property::LikeOf
+    } else if is("u-repost-of") {
+        Property::RepostOf
+    } else if is("u-in-reply-to") {
+        Property::InReplyTo
+    } else if is("u-bookmark-of") {
+        Property::BookmarkOf
+    } else {
+        Property::MentionOf
+    };
+    // markup from strangers is not passed on, readers get the text
+    let content = find(&entry, "e-content").map(|c| Content {
+        text: text(&c),
+        html: String::new(),
+    });
+    let published = find(&entry, "dt-published").map(|p| {
+        p.value()
+            .attr("datetime")
+            .map(str::to_owned)
+            .unwrap_or_else(|| text(&p))
+    });
+    let url = entry
+        .select(&selector(".u-url"))
+        .find(|u| {
+            !u.ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|a| has_class(&a, "h-card") || has_class(&a, "h-cite"))
+        })
+        .map(|u| u_value(&u, source))
+        .unwrap_or_else(|| source.to_string());
+    Mention {
+        url,
+        author: author(&entry, source),
+        content,
+        published,
+        wm_property,
+        in_reply_to: (wm_property == Property::InReplyTo).then(|| target.to_owned()),
+        ..Default::default()
+    }
+}
+
+/// does the page link to `target`
+pub fn links_to(html: &str, source: &Url, target: &str) -> bool {
+    let Ok(target) = Url::parse(target) else {
+        return false;
+    };
+    let document = Html::parse_document(html);
+    document
+        .select(&selector("a[href], link[href], img[src], video[src], audio[src]"))
+        .filter_map(|element| element.value().attr("href").or_else(|| element.value().attr("src")))
+        .filter_map(|href| source.join(href).ok())
+        .any(|mut url| {
+            url.set_fragment(None);
+            url == target
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TARGET: &str = "https://blog.gyara.moe/post/a/";
+
+    fn source() -> Url {
+        Url::parse("https://example.com/notes/1").unwrap()
+    }
+
+    #[test]
+    fn reply() {
+        let html = r#"<article class="h-entry">
+            <a class="p-author h-card" href="/"><img class="u-photo" src="/me.jpg"><span class="p-name">Alice</span></a>
+            <div class="u-in-reply-to h-cite"><a class="u-url" href="https://blog.gyara.moe/post/a/">a</a></div>
+            <div class="e-content"><p>Nice   <b>post</b></p></div>
+            <time class="dt-published" datetime="2023-01-02T03:04:05Z">Jan 2</time>
+            <a class="u-url" href="/notes/1#x">link</a>
+        </article>"#;
+        let mention = parse(html, &source(), TARGET);
+        assert_eq!(mention.wm_property, Property::InReplyTo);
+        assert_eq!(mention.in_reply_to.as_deref(), Some(TARGET));
+        assert_eq!(mention.author.name, "Alice");
+        assert_eq!(mention.author.photo, "https://example.com/me.jpg");
+        assert_eq!(mention.author.url, "https://example.com/");
+        assert_eq!(mention.content.unwrap().text, "Nice post");
+        assert_eq!(mention.published.as_deref(), Some("2023-01-02T03:04:05Z"));
+        assert_eq!(mention.url, "https://example.com/notes/1#x");
+        assert!(links_to(html, &source(), TARGET));
+    }
+
+    #[test]
+    fn like_and_plain() {
+        let like = r#"<div class="h-entry"><a class="u-like-of" href="https://blog.gyara.moe/post/a/">♥</a></div>"#;
+        assert_eq!(parse(like, &source(), TARGET).wm_property, Property::LikeOf);
+        let plain = r#"<p>see <a href="https://blog.gyara.moe/post/a/#top">this</a></p>"#;
+        let mention = parse(plain, &source(), TARGET);
+        assert_eq!(mention.wm_property, Property::MentionOf);
+        assert_eq!(mention.url, "https://example.com/notes/1");
+        assert!(links_to(plain, &source(), TARGET));
+        assert!(!links_to("<p>https://blog.gyara.moe/post/a/</p>", &source(), TARGET));
+    }
+}
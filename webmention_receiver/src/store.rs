@@ -0,0 +1,97 @@
+//! mentions in an embedded database, keyed by target then source
+use fubuki_types::webmention::Mention;
+use redb::{Database, TableDefinition};
+use std::path::Path;
+
+const MENTIONS: TableDefinition<&str, &str> = TableDefinition::new("mentions");
+
+pub struct Store {
+    db: Database,
+}
+
+/// `\u{1}` sorts before every url character, so a target's mentions are one range
+fn key(target: &str, source: &str) -> String {
+    format!("{}\u{1}{}", target, source)
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let db = Database::create(path)?;
+        // reads fail on a table never written
+        let tx = db.begin_write()?;
+        tx.open_table(MENTIONS)?;
+        tx.commit()?;
+        Ok(Self { db })
+    }
+
+    pub fn put(&self, target: &str, source: &str, mention: &Mention) -> anyhow::Result<()> {
+        let json = serde_json::to_string(mention)?;
+        let tx = self.db.begin_write()?;
+        tx.open_table(MENTIONS)?
+            .insert(key(target, source).as_str(), json.as_str())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get(&self, target: &str, source: &str) -> anyhow::Result<Option<Mention>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(MENTIONS)?;
+        let value = table.get(key(target, source).as_str())?;
+        Ok(match value {
+            Some(json) => Some(serde_json::from_str(json.value())?),
+            None => None,
+        })
+    }
+
+    pub fn delete(&self, target: &str, source: &str) -> anyhow::Result<()> {
+        let tx = self.db.begin_write()?;
+        tx.open_table(MENTIONS)?.remove(key(target, source).as_str())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// mentions of `target`, oldest first
+    pub fn mentions(&self, target: &str) -> anyhow::Result<Vec<Mention>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(MENTIONS)?;
+        let (start, end) = (format!("{}\u{1}", target), format!("{}\u{2}", target));
+        let mut mentions = Vec::new();
+        for item in table.range(start.as_str()..end.as_str())? {
+            let (_, json) = item?;
+            mentions.push(serde_json::from_str::<Mention>(json.value())?);
+        }
+        mentions.sort_by(|a, b| a.date().cmp(b.date()));
+        Ok(mentions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn by_target() {
+        let path = std::env::temp_dir().join(format!("fubuki-store-{}.redb", std::process::id()));
+        let store = Store::open(&path).unwrap();
+        let mention = |url: &str, date: &str| Mention {
+            url: url.to_owned(),
+            wm_received: date.to_owned(),
+            ..Default::default()
+        };
+        store.put("https://a/x", "https://s/2", &mention("2", "2")).unwrap();
+        store.put("https://a/x", "https://s/1", &mention("1", "1")).unwrap();
+        store.put("https://a/xy", "https://s/3", &mention("3", "3")).unwrap();
+        let urls: Vec<String> = store
+            .mentions("https://a/x")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.url)
+            .collect();
+        assert_eq!(urls, ["1", "2"]);
+        store.delete("https://a/x", "https://s/1").unwrap();
+        assert_eq!(store.mentions("https://a/x").unwrap().len(), 1);
+        assert!(store.get("https://a/x", "https://s/1").unwrap().is_none());
+        drop(store);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! the asynchronous half, fetch the source and decide what the mention is
+//! Sources only ever resolve to public addresses, so a mention can not make us
+//! request the machine we run on or its network.
+use crate::{mf2, store::Store};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    StatusCode,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use url::{Host, Url};
+
+/// a received `source` and `target`, already checked for shape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub source: Url,
+    pub target: Url,
+}
+
+/// only this much of a source is read, a link after it does not count
+const MAX_SOURCE: usize = 1024 * 1024;
+
+/// not loopback, private, link-local or otherwise reserved
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // shared address space 100.64.0.0/10
+        || (a == 100 && b & 0xc0 == 64)
+        || a == 0
+        || a >= 240)
+}
+
+/// hosts written as an address are checked here, names by the resolver
+pub fn is_public_host(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => is_public(ip.into()),
+        Some(Host::Ipv6(ip)) => is_public(ip.into()),
+        Some(Host::Domain(domain)) => domain != "localhost" && !domain.ends_with(".localhost"),
+        None => false,
+    }
+}
+
+/// drops every private address a name resolves to
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// the client sources are fetched with
+pub fn client() -> reqwest::Result<reqwest::Client> {
+    let redirect = Policy::custom(|attempt| {
+        if attempt.previous().len() >= 5 {
+            attempt.error("too many redirects")
+        } else if !is_public_host(attempt.url()) {
+            attempt.error("redirected to a private address")
+        } else {
+            attempt.follow()
+        }
+    });
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(20))
+        .redirect(redirect)
+        .dns_resolver(Arc::new(PublicResolver))
+        .user_agent(concat!("fubuki webmention_receiver/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// at most `MAX_SOURCE` bytes of the body, the rest is never downloaded
+async fn read_body(mut response: reqwest::Response) -> reqwest::Result<String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = MAX_SOURCE - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if body.len() == MAX_SOURCE {
+            break;
+        }
+    }
+    // a cut inside a character loses only that character
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+pub enum Outcome {
+    Saved,
+    /// the source is gone or no longer links to the target
+    Deleted,
+}
+
+pub async fn verify(client: &reqwest::Client, store: &Store, job: &Job) -> anyhow::Result<Outcome> {
+    let (source, target) = (job.source.as_str(), job.target.as_str());
+    let response = client.get(source).header("Accept", "text/html").send().await?;
+    if matches!(response.status(), StatusCode::GONE | StatusCode::NOT_FOUND) {
+        store.delete(target, source)?;
+        return Ok(Outcome::Deleted);
+    }
+    let response = response.error_for_status()?;
+    let final_url = response.url().clone();
+    let body = read_body(response).await?;
+    let body = body.as_str();
+    if !mf2::links_to(body, &final_url, target) {
+        store.delete(target, source)?;
+        return Ok(Outcome::Deleted);
+    }
+    let mut mention = mf2::parse(body, &final_url, target);
+    // an update keeps the first time we saw it
+    mention.wm_received = match store.get(target, source)? {
+        Some(old) => old.wm_received,
+        None => chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    };
+    store.put(target, source, &mention)?;
+    Ok(Outcome::Saved)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+
+    #[test]
+    fn private_hosts() {
+        for url in [
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://192.168.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/",
+            "http://[::1]/",
+            "http://[fe80::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://localhost:8080/",
+        ] {
+            assert!(!is_public_host(&Url::parse(url).unwrap()), "{}", url);
+        }
+        for url in ["https://93.184.216.34/", "https://[2606:4700::1111]/", "https://a.com/"] {
+            assert!(is_public_host(&Url::parse(url).unwrap()), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_source() {
+        let app = Router::new()
+            .route(
+                "/like",
+                get(|| async {
+                    axum::response::Html(
+                        r#"<div class="h-entry"><a class="u-like-of" href="https://blog.gyara.moe/post/a/">♥</a></div>"#,
+                    )
+                }),
+            )
+            .route("/gone", get(|| async { StatusCode::GONE }))
+            // cut in the middle of a character
+            .route("/large", get(|| async { "あ".repeat(MAX_SOURCE / 3 + 1) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let path = std::env::temp_dir().join(format!("fubuki-verify-{}.redb", std::process::id()));
+        let store = Store::open(&path).unwrap();
+        let client = reqwest::Client::new();
+        let target = Url::parse("https://blog.gyara.moe/post/a/").unwrap();
+        let job = |path: &str| Job {
+            source: Url::parse(&format!("{}{}", base, path)).unwrap(),
+            target: target.clone(),
+        };
+        assert!(matches!(
+            verify(&client, &store, &job("/like")).await.unwrap(),
+            Outcome::Saved
+        ));
+        let mentions = store.mentions(target.as_str()).unwrap();
+        assert_eq!(mentions[0].wm_property, fubuki_types::webmention::Property::LikeOf);
+        assert!(!mentions[0].wm_received.is_empty());
+        // a source that went away takes its mention with it
+        store
+            .put(target.as_str(), job("/gone").source.as_str(), &mentions[0])
+            .unwrap();
+        assert!(matches!(
+            verify(&client, &store, &job("/gone")).await.unwrap(),
+            Outcome::Deleted
+        ));
+        assert_eq!(store.mentions(target.as_str()).unwrap().len(), 1);
+        assert!(matches!(
+            verify(&client, &store, &job("/large")).await.unwrap(),
+            Outcome::Deleted
+        ));
+        drop(store);
+        std::fs::remove_file(path).unwrap();
+    }
+}